@@ -0,0 +1,62 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use graphviz3d::generators::{grid, random_tree};
+use graphviz3d::{layout, LayoutParams};
+
+fn layout_benchmark(c: &mut Criterion) {
+    let params = LayoutParams {
+        seed: Some(0),
+        ..Default::default()
+    };
+    let mut group = c.benchmark_group("layout");
+    // Full solves of the larger graphs take seconds each
+    group.sample_size(10);
+    for size in [100, 1000, 5000] {
+        let tree = random_tree(size, 0);
+        group.bench_with_input(BenchmarkId::new("random_tree", size), &tree, |b, g| {
+            b.iter(|| layout(g, &params))
+        });
+        let side = (size as f32).sqrt().round() as usize;
+        let grid = grid(side, side);
+        group.bench_with_input(BenchmarkId::new("grid", side * side), &grid, |b, g| {
+            b.iter(|| layout(g, &params))
+        });
+    }
+    group.finish();
+
+    // The exact repulsion visits every pair of nodes
+    let params = LayoutParams {
+        exact_repulsion: true,
+        ..params
+    };
+    let mut group = c.benchmark_group("exact_repulsion");
+    group.sample_size(10);
+    for size in [100, 1000] {
+        let tree = random_tree(size, 0);
+        group.bench_with_input(BenchmarkId::new("random_tree", size), &tree, |b, g| {
+            b.iter(|| layout(g, &params))
+        });
+    }
+    group.finish();
+
+    // Starting spread out in proportion to the size of the graph needs fewer
+    // iterations to settle, see init_radius_convergence_test
+    let mut group = c.benchmark_group("init_radius");
+    group.sample_size(10);
+    let tree = random_tree(2000, 0);
+    for scale_init_radius in [false, true] {
+        let params = LayoutParams {
+            seed: Some(0),
+            scale_init_radius,
+            ..Default::default()
+        };
+        group.bench_with_input(
+            BenchmarkId::new("random_tree_2000", scale_init_radius),
+            &tree,
+            |b, g| b.iter(|| layout(g, &params)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, layout_benchmark);
+criterion_main!(benches);
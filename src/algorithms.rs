@@ -0,0 +1,559 @@
+//! Graph algorithms over flattened edge lists.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+/// Returns the number of connected components, ignoring edge direction, and
+/// the component of each node. Components are numbered in order of their
+/// lowest node index.
+pub fn connected_components(
+    num_points: usize,
+    edges_indices: &[(usize, usize)],
+) -> (usize, Vec<usize>) {
+    let mut parents = (0..num_points).collect::<Vec<_>>();
+    for &(i, j) in edges_indices {
+        let (a, b) = (find(&mut parents, i), find(&mut parents, j));
+        if a != b {
+            parents[a.max(b)] = a.min(b);
+        }
+    }
+
+    let mut component_ids = vec![usize::MAX; num_points];
+    let mut components = vec![0; num_points];
+    let mut num_components = 0;
+    for (i, component) in components.iter_mut().enumerate() {
+        let root = find(&mut parents, i);
+        if component_ids[root] == usize::MAX {
+            component_ids[root] = num_components;
+            num_components += 1;
+        }
+        *component = component_ids[root];
+    }
+    (num_components, components)
+}
+
+/// Returns the root of the union-find set containing `i`, halving the path.
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Returns the number of strongly connected components, following edges from
+/// their first to their second node, and the component of each node. Nodes on
+/// a common cycle share a component.
+pub fn strongly_connected_components(
+    num_points: usize,
+    edges_indices: &[(usize, usize)],
+) -> (usize, Vec<usize>) {
+    let mut outgoing = vec![Vec::new(); num_points];
+    let mut incoming = vec![Vec::new(); num_points];
+    for &(i, j) in edges_indices {
+        outgoing[i].push(j);
+        incoming[j].push(i);
+    }
+
+    // Kosaraju: order the nodes by when their depth-first search finishes...
+    let mut visited = vec![false; num_points];
+    let mut order = Vec::with_capacity(num_points);
+    for start in 0..num_points {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut stack = vec![(start, 0)];
+        while let Some(top) = stack.last_mut() {
+            let (i, next) = *top;
+            top.1 += 1;
+            match outgoing[i].get(next) {
+                Some(&j) if !visited[j] => {
+                    visited[j] = true;
+                    stack.push((j, 0));
+                }
+                Some(_) => (),
+                None => {
+                    order.push(i);
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    // ...then collect what reaches each node against the edges, latest first
+    let mut components = vec![usize::MAX; num_points];
+    let mut num_components = 0;
+    for &start in order.iter().rev() {
+        if components[start] != usize::MAX {
+            continue;
+        }
+        components[start] = num_components;
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            for &j in &incoming[i] {
+                if components[j] == usize::MAX {
+                    components[j] = num_components;
+                    stack.push(j);
+                }
+            }
+        }
+        num_components += 1;
+    }
+    (num_components, components)
+}
+
+/// Returns for each edge whether it is a back edge of a depth-first search
+/// that follows edges from their first to their second node, starting from
+/// the nodes in order. Back edges lead to a node whose search is still in
+/// progress, so each one closes a cycle and a graph without them is acyclic.
+/// Self-loops are always back edges.
+pub fn back_edges(num_points: usize, edges_indices: &[(usize, usize)]) -> Vec<bool> {
+    let mut outgoing = vec![Vec::new(); num_points];
+    for (e, &(i, j)) in edges_indices.iter().enumerate() {
+        outgoing[i].push((j, e));
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Unvisited,
+        InProgress,
+        Done,
+    }
+    let mut states = vec![State::Unvisited; num_points];
+    let mut back = vec![false; edges_indices.len()];
+    for start in 0..num_points {
+        if states[start] != State::Unvisited {
+            continue;
+        }
+        states[start] = State::InProgress;
+        let mut stack = vec![(start, 0)];
+        while let Some(top) = stack.last_mut() {
+            let (i, next) = *top;
+            top.1 += 1;
+            match outgoing[i].get(next) {
+                Some(&(j, e)) => match states[j] {
+                    State::Unvisited => {
+                        states[j] = State::InProgress;
+                        stack.push((j, 0));
+                    }
+                    State::InProgress => back[e] = true,
+                    State::Done => (),
+                },
+                None => {
+                    states[i] = State::Done;
+                    stack.pop();
+                }
+            }
+        }
+    }
+    back
+}
+
+/// Returns for each edge whether it is part of a minimum spanning forest,
+/// ignoring edge direction. Ties are broken by edge order, and self-loops are
+/// never included.
+pub fn minimum_spanning_tree(
+    num_points: usize,
+    edges_indices: &[(usize, usize)],
+    weights: &[f32],
+) -> Vec<bool> {
+    let mut order = (0..edges_indices.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| weights[a].total_cmp(&weights[b]));
+    let mut parents = (0..num_points).collect::<Vec<_>>();
+    let mut in_tree = vec![false; edges_indices.len()];
+    for e in order {
+        let (i, j) = edges_indices[e];
+        let (a, b) = (find(&mut parents, i), find(&mut parents, j));
+        if a != b {
+            parents[a.max(b)] = a.min(b);
+            in_tree[e] = true;
+        }
+    }
+    in_tree
+}
+
+/// Projects the first `dims` coordinates of each point in `points`, which
+/// holds `stride` coordinates per point, onto the `out_dims` directions of
+/// largest variance. Returns `out_dims` coordinates per point, centered on the
+/// mean and ordered by decreasing variance.
+pub fn principal_components(
+    points: &[f32],
+    stride: usize,
+    dims: usize,
+    out_dims: usize,
+) -> Vec<f32> {
+    let num_points = points.len() / stride;
+    if num_points == 0 {
+        return Vec::new();
+    }
+    let mut mean = vec![0.0; dims];
+    for p in points.chunks(stride) {
+        for (m, v) in mean.iter_mut().zip(p) {
+            *m += *v as f64 / num_points as f64;
+        }
+    }
+    let mut covariance = vec![0.0; dims * dims];
+    for p in points.chunks(stride) {
+        for a in 0..dims {
+            for b in 0..dims {
+                covariance[a * dims + b] +=
+                    (p[a] as f64 - mean[a]) * (p[b] as f64 - mean[b]) / num_points as f64;
+            }
+        }
+    }
+
+    // Power iteration, removing each component from the covariance once found
+    // and keeping each iterate orthogonal to the axes found before it, which
+    // the deflation alone leaves to rounding
+    let trace = (0..dims).map(|a| covariance[a * dims + a]).sum::<f64>();
+    let orthogonalize = |v: &mut [f64], axes: &[Vec<f64>]| {
+        for axis in axes {
+            let dot = v.iter().zip(axis).map(|(x, y)| x * y).sum::<f64>();
+            for (x, y) in v.iter_mut().zip(axis) {
+                *x -= dot * y;
+            }
+        }
+    };
+    let mut axes = Vec::with_capacity(out_dims);
+    for k in 0..out_dims.min(dims) {
+        let mut v = (0..dims)
+            .map(|a| if a == k { 1.0 } else { 0.1 })
+            .collect::<Vec<f64>>();
+        orthogonalize(&mut v, &axes);
+        let mut eigenvalue = 0.0;
+        for _ in 0..200 {
+            let mut w = (0..dims)
+                .map(|a| {
+                    (0..dims)
+                        .map(|b| covariance[a * dims + b] * v[b])
+                        .sum::<f64>()
+                })
+                .collect::<Vec<_>>();
+            orthogonalize(&mut w, &axes);
+            let norm = w.iter().map(|x| x * x).sum::<f64>().sqrt();
+            if norm <= 1e-9 * trace {
+                eigenvalue = 0.0;
+                break;
+            }
+            eigenvalue = norm;
+            v = w.iter().map(|x| x / norm).collect();
+        }
+        // No variance left, so the remaining coordinates are 0
+        if eigenvalue == 0.0 {
+            v = vec![0.0; dims];
+        }
+        for a in 0..dims {
+            for b in 0..dims {
+                covariance[a * dims + b] -= eigenvalue * v[a] * v[b];
+            }
+        }
+        axes.push(v);
+    }
+
+    let mut projected = vec![0.0; num_points * out_dims];
+    for (p, out) in points.chunks(stride).zip(projected.chunks_mut(out_dims)) {
+        for (o, axis) in out.iter_mut().zip(&axes) {
+            *o = (0..dims)
+                .map(|a| (p[a] as f64 - mean[a]) * axis[a])
+                .sum::<f64>() as f32;
+        }
+    }
+    projected
+}
+
+/// Returns the nodes and the indices of the edges along a shortest path from
+/// `from` to `to`, or `None` if there is none. With `directed`, edges are only
+/// followed from their first to their second node.
+pub fn shortest_path(
+    num_points: usize,
+    edges_indices: &[(usize, usize)],
+    from: usize,
+    to: usize,
+    directed: bool,
+) -> Option<(Vec<usize>, Vec<usize>)> {
+    let mut neighbors = vec![Vec::new(); num_points];
+    for (e, &(i, j)) in edges_indices.iter().enumerate() {
+        neighbors[i].push((j, e));
+        if !directed {
+            neighbors[j].push((i, e));
+        }
+    }
+
+    // The edge each node was first reached by
+    let mut reached_by = vec![None; num_points];
+    let mut visited = vec![false; num_points];
+    visited[from] = true;
+    let mut queue = VecDeque::from([from]);
+    while let Some(i) = queue.pop_front() {
+        if i == to {
+            break;
+        }
+        for &(j, e) in &neighbors[i] {
+            if !visited[j] {
+                visited[j] = true;
+                reached_by[j] = Some((i, e));
+                queue.push_back(j);
+            }
+        }
+    }
+    if !visited[to] {
+        return None;
+    }
+
+    let mut nodes = vec![to];
+    let mut edges = Vec::new();
+    while let Some((i, e)) = reached_by[*nodes.last().unwrap()] {
+        nodes.push(i);
+        edges.push(e);
+    }
+    nodes.reverse();
+    edges.reverse();
+    Some((nodes, edges))
+}
+
+/// Returns the number of edges touching each node. Self-loops count twice.
+pub fn degrees(num_points: usize, edges_indices: &[(usize, usize)]) -> Vec<usize> {
+    let mut degrees = vec![0; num_points];
+    for &(i, j) in edges_indices {
+        degrees[i] += 1;
+        degrees[j] += 1;
+    }
+    degrees
+}
+
+/// Returns the PageRank of each node, which sums to 1. With `directed`, rank
+/// flows from the first to the second node of each edge, otherwise both ways.
+/// `damping` is the probability of following an edge rather than jumping to
+/// a random node, and nodes without outgoing edges spread their rank evenly.
+pub fn pagerank(
+    num_points: usize,
+    edges_indices: &[(usize, usize)],
+    directed: bool,
+    damping: f32,
+    iterations: usize,
+) -> Vec<f32> {
+    if num_points == 0 {
+        return Vec::new();
+    }
+    let n = num_points as f32;
+    let mut links = Vec::with_capacity(2 * edges_indices.len());
+    for &(i, j) in edges_indices {
+        links.push((i, j));
+        if !directed && i != j {
+            links.push((j, i));
+        }
+    }
+    let mut out_degrees = vec![0; num_points];
+    for &(i, _) in &links {
+        out_degrees[i] += 1;
+    }
+    let mut ranks = vec![1.0 / n; num_points];
+    for _ in 0..iterations {
+        let dangling = ranks
+            .iter()
+            .zip(&out_degrees)
+            .filter(|(_, &d)| d == 0)
+            .map(|(r, _)| r)
+            .sum::<f32>();
+        let base = (1.0 - damping) / n + damping * dangling / n;
+        let mut next = vec![base; num_points];
+        for &(i, j) in &links {
+            next[j] += damping * ranks[i] / out_degrees[i] as f32;
+        }
+        ranks = next;
+    }
+    ranks
+}
+
+/// Returns the shortest path distance between all pairs of nodes, ignoring edge
+/// direction, as a row-major `num_points * num_points` matrix. Unreachable
+/// pairs are `f32::INFINITY`. Uses breadth-first search when all `lengths` are
+/// equal and Dijkstra otherwise.
+pub fn all_pairs_distances(
+    num_points: usize,
+    edges_indices: &[(usize, usize)],
+    lengths: &[f32],
+) -> Vec<f32> {
+    let mut neighbors = vec![Vec::new(); num_points];
+    for (&(i, j), &length) in edges_indices.iter().zip(lengths) {
+        if length.is_finite() {
+            neighbors[i].push((j, length));
+            neighbors[j].push((i, length));
+        }
+    }
+
+    let uniform = lengths.windows(2).all(|w| w[0] == w[1]);
+    let mut distances = vec![f32::INFINITY; num_points * num_points];
+    for (source, row) in distances.chunks_mut(num_points.max(1)).enumerate() {
+        if uniform {
+            breadth_first(&neighbors, source, row);
+        } else {
+            dijkstra(&neighbors, source, row);
+        }
+    }
+    distances
+}
+
+fn breadth_first(neighbors: &[Vec<(usize, f32)>], source: usize, distances: &mut [f32]) {
+    let mut queue = VecDeque::from([source]);
+    distances[source] = 0.0;
+    while let Some(i) = queue.pop_front() {
+        for &(j, length) in &neighbors[i] {
+            if distances[j].is_infinite() {
+                distances[j] = distances[i] + length;
+                queue.push_back(j);
+            }
+        }
+    }
+}
+
+/// Entry of the Dijkstra queue, ordered so the closest node is popped first.
+#[derive(PartialEq)]
+struct Visit(f32, usize);
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0)
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn dijkstra(neighbors: &[Vec<(usize, f32)>], source: usize, distances: &mut [f32]) {
+    let mut queue = BinaryHeap::from([Visit(0.0, source)]);
+    distances[source] = 0.0;
+    while let Some(Visit(distance, i)) = queue.pop() {
+        if distance > distances[i] {
+            continue;
+        }
+        for &(j, length) in &neighbors[i] {
+            let d = distance + length;
+            if d < distances[j] {
+                distances[j] = d;
+                queue.push(Visit(d, j));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn connected_components_test() {
+        let (count, components) = connected_components(6, &[(0, 1), (3, 2), (1, 4)]);
+        assert_eq!(count, 3);
+        assert_eq!(components, vec![0, 0, 1, 1, 0, 2]);
+    }
+
+    #[test]
+    fn strongly_connected_components_test() {
+        let edges = [(0, 1), (1, 2), (2, 0), (2, 3), (3, 4), (4, 3), (5, 0)];
+        let (count, c) = strongly_connected_components(6, &edges);
+        assert_eq!(count, 3);
+        assert!(c[0] == c[1] && c[1] == c[2]);
+        assert_eq!(c[3], c[4]);
+        assert!(c[0] != c[3] && c[0] != c[5] && c[3] != c[5]);
+    }
+
+    #[test]
+    fn back_edges_test() {
+        // A cycle 0 -> 1 -> 2 -> 0, a shortcut 0 -> 2, a self-loop and a
+        // cycle reached from the start only later
+        let edges = [(0, 1), (1, 2), (2, 0), (0, 2), (3, 3), (4, 5), (5, 4)];
+        assert_eq!(
+            back_edges(6, &edges),
+            vec![false, false, true, false, true, false, true]
+        );
+        assert_eq!(back_edges(3, &[(0, 1), (1, 2), (0, 2)]), vec![false; 3]);
+    }
+
+    #[test]
+    fn minimum_spanning_tree_test() {
+        let edges = [(0, 1), (1, 2), (0, 2), (2, 2), (3, 4)];
+        let weights = [1.0, 3.0, 2.0, 0.0, 5.0];
+        assert_eq!(
+            minimum_spanning_tree(5, &edges, &weights),
+            vec![true, false, true, false, true]
+        );
+    }
+
+    #[test]
+    fn principal_components_test() {
+        // Points spread along the last axis, with a little noise in another
+        let points = (0..5)
+            .flat_map(|i| {
+                let t = i as f32 - 2.0;
+                [0.0, 0.1 * (i % 2) as f32, 0.0, 0.0, 3.0 * t, 7.0]
+            })
+            .collect::<Vec<_>>();
+        let projected = principal_components(&points, 6, 5, 3);
+        assert_eq!(projected.len(), 15);
+        for (i, p) in projected.chunks(3).enumerate() {
+            let t = i as f32 - 2.0;
+            assert!((p[0].abs() - 3.0 * t.abs()).abs() < 1e-3, "{:?}", p);
+            assert!(p[1].abs() < 0.1, "{:?}", p);
+            // Only two coordinates vary, so there is no third axis
+            assert_eq!(p[2], 0.0, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn shortest_path_test() {
+        let edges = [(0, 1), (1, 2), (0, 3), (3, 2), (2, 4), (4, 0)];
+        assert_eq!(
+            shortest_path(6, &edges, 0, 4, true),
+            Some((vec![0, 1, 2, 4], vec![0, 1, 4]))
+        );
+        assert_eq!(
+            shortest_path(6, &edges, 0, 4, false),
+            Some((vec![0, 4], vec![5]))
+        );
+        assert_eq!(
+            shortest_path(6, &edges, 0, 0, true),
+            Some((vec![0], vec![]))
+        );
+        assert_eq!(shortest_path(6, &edges, 0, 5, false), None);
+    }
+
+    #[test]
+    fn degrees_test() {
+        assert_eq!(degrees(4, &[(0, 1), (1, 2), (1, 1)]), vec![1, 4, 1, 0]);
+    }
+
+    #[test]
+    fn pagerank_test() {
+        // Everything links to 0, which links back to 1
+        let edges = [(1, 0), (2, 0), (3, 0), (0, 1)];
+        let ranks = pagerank(4, &edges, true, 0.85, 50);
+        assert!((ranks.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        assert!(ranks[0] > ranks[1] && ranks[1] > ranks[2]);
+        assert!((ranks[2] - ranks[3]).abs() < 1e-6);
+
+        // Without direction a path ranks its middle highest
+        let ranks = pagerank(3, &[(0, 1), (1, 2)], false, 0.85, 50);
+        assert!(ranks[1] > ranks[0]);
+        assert!((ranks[0] - ranks[2]).abs() < 1e-6);
+
+        // A node without edges keeps an even share
+        assert_eq!(pagerank(1, &[], true, 0.85, 10), vec![1.0]);
+        assert!(pagerank(0, &[], true, 0.85, 10).is_empty());
+    }
+
+    #[test]
+    fn all_pairs_distances_test() {
+        let edges = [(0, 1), (1, 2), (3, 2)];
+        let d = all_pairs_distances(5, &edges, &[1.0, 1.0, 1.0]);
+        assert_eq!(&d[0..5], &[0.0, 1.0, 2.0, 3.0, f32::INFINITY]);
+        let d = all_pairs_distances(5, &edges, &[1.0, 4.0, 1.0]);
+        assert_eq!(&d[5..10], &[1.0, 0.0, 4.0, 5.0, f32::INFINITY]);
+    }
+}
@@ -0,0 +1,112 @@
+//! Helpers for reading DOT attribute values.
+
+use graphviz_rust::dot_structures::*;
+
+/// Returns the text of `id`, without the surrounding quotes of escaped ids.
+pub fn id_value(id: &Id) -> &str {
+    match id {
+        Id::Escaped(s) => s
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .unwrap_or(s),
+        Id::Html(s) | Id::Plain(s) | Id::Anonymous(s) => s,
+    }
+}
+
+/// Returns the value of the last attribute named `key`.
+pub fn attribute_value<'a>(attributes: &'a [Attribute], key: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .rev()
+        .find(|a| id_value(&a.0) == key)
+        .map(|a| id_value(&a.1))
+}
+
+/// Returns the graph attributes set directly in `stmts`, either as `key=value`
+/// statements or in `graph [...]` lists, in declaration order.
+pub fn graph_attributes(stmts: &[Stmt]) -> Vec<Attribute> {
+    let mut attributes = Vec::new();
+    for s in stmts {
+        match s {
+            Stmt::Attribute(a) => attributes.push(a.clone()),
+            Stmt::GAttribute(GraphAttributes::Graph(list)) => {
+                attributes.extend(list.iter().cloned())
+            }
+            _ => (),
+        }
+    }
+    attributes
+}
+
+/// Returns true if a node should stay at its `pos`, either because of
+/// `pin=true` or because the position ends with `!`.
+pub fn is_pinned(attributes: &[Attribute]) -> bool {
+    let pin = attribute_value(attributes, "pin")
+        .is_some_and(|v| matches!(v.trim().to_lowercase().as_str(), "true" | "yes" | "1"));
+    let pos = attribute_value(attributes, "pos").is_some_and(|v| v.trim().ends_with('!'));
+    pin || pos
+}
+
+/// Parses a `pos` value like `"x,y"` or `"x,y,z"`, ignoring the trailing `!`
+/// that marks pinned positions.
+pub fn parse_pos(value: &str) -> Option<Vec<f32>> {
+    let coords = value
+        .trim()
+        .trim_end_matches('!')
+        .split(',')
+        .map(|v| v.trim().parse::<f32>().ok())
+        .collect::<Option<Vec<f32>>>()?;
+    match coords.len() {
+        2 | 3 => Some(coords),
+        _ => None,
+    }
+}
+
+/// Parses an edge `weight`, defaulting to 1 when absent or malformed. Negative
+/// weights are clamped to 0.
+pub fn parse_weight(value: Option<&str>) -> f32 {
+    value
+        .and_then(|v| v.trim().parse::<f32>().ok())
+        .unwrap_or(1.0)
+        .max(0.0)
+}
+
+/// Direction of the hierarchy given by the graph `rankdir` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankDir {
+    /// `TB`, the default.
+    #[default]
+    TopToBottom,
+    /// `BT`
+    BottomToTop,
+    /// `LR`
+    LeftToRight,
+    /// `RL`
+    RightToLeft,
+}
+
+impl RankDir {
+    /// Parses a `rankdir` value, defaulting to top to bottom.
+    pub fn parse(value: Option<&str>) -> Self {
+        match value.map(|v| v.trim().to_uppercase()).as_deref() {
+            Some("BT") => RankDir::BottomToTop,
+            Some("LR") => RankDir::LeftToRight,
+            Some("RL") => RankDir::RightToLeft,
+            _ => RankDir::TopToBottom,
+        }
+    }
+
+    /// Returns the axis edges are oriented along, and the sign of the
+    /// direction from an edge target to its source. `TB` and `BT` use
+    /// `vertical_axis`, while `LR` and `RL` use axis 0, or axis 2 if the
+    /// vertical axis is 0.
+    pub fn axis_and_sign(self, vertical_axis: usize) -> (usize, f32) {
+        let horizontal_axis = if vertical_axis == 0 { 2 } else { 0 };
+        match self {
+            RankDir::TopToBottom => (vertical_axis, 1.0),
+            RankDir::BottomToTop => (vertical_axis, -1.0),
+            RankDir::LeftToRight => (horizontal_axis, -1.0),
+            RankDir::RightToLeft => (horizontal_axis, 1.0),
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Barnes-Hut approximation of the node repulsion pass.
+//!
+//! The tree partitions the first three coordinates of the points into an
+//! octree. Distant cells are summarized by their center of mass, which is kept
+//! in all dimensions so the approximation also works while annealing. The
+//! mass of a point is how strongly it repels, and a cell's mass is the sum
+//! over its points.
+
+use crate::forces::{distance, repel};
+use crate::LayoutParams;
+
+/// Cells with at most this many points are not subdivided further.
+const LEAF_SIZE: usize = 8;
+
+/// Stop subdividing at this depth, which only happens for coincident points.
+const MAX_DEPTH: usize = 16;
+
+struct Cell {
+    min: [f32; 3],
+    size: f32,
+    center: Vec<f32>,
+    mass: f32,
+    children: Vec<usize>,
+    members: Vec<usize>,
+}
+
+pub(crate) struct Octree<'a> {
+    cells: Vec<Cell>,
+    stride: usize,
+    masses: &'a [f32],
+}
+
+impl<'a> Octree<'a> {
+    /// Builds the tree over `points`, where point `i` has mass `masses[i]`.
+    pub(crate) fn new(points: &[f32], stride: usize, masses: &'a [f32]) -> Self {
+        let num_points = points.len() / stride;
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for p in points.chunks(stride) {
+            for k in 0..3 {
+                min[k] = min[k].min(p[k]);
+                max[k] = max[k].max(p[k]);
+            }
+        }
+        let size = (0..3).map(|k| max[k] - min[k]).fold(0.0, f32::max) + 0.001;
+        let mut tree = Octree {
+            cells: Vec::new(),
+            stride,
+            masses,
+        };
+        if num_points > 0 {
+            tree.build(points, (0..num_points).collect(), min, size, 0);
+        }
+        tree
+    }
+
+    fn build(
+        &mut self,
+        points: &[f32],
+        members: Vec<usize>,
+        min: [f32; 3],
+        size: f32,
+        depth: usize,
+    ) -> usize {
+        let stride = self.stride;
+        let mut center = vec![0.0; stride];
+        let mut mass = 0.0;
+        for &i in &members {
+            let m = self.masses[i];
+            for (c, v) in center.iter_mut().zip(&points[i * stride..(i + 1) * stride]) {
+                *c += v * m;
+            }
+            mass += m;
+        }
+        for c in &mut center {
+            *c /= mass;
+        }
+
+        let index = self.cells.len();
+        self.cells.push(Cell {
+            min,
+            size,
+            center,
+            mass,
+            children: Vec::new(),
+            members: Vec::new(),
+        });
+
+        if members.len() <= LEAF_SIZE || depth >= MAX_DEPTH {
+            self.cells[index].members = members;
+            return index;
+        }
+
+        let half = size * 0.5;
+        let mut octants: [Vec<usize>; 8] = Default::default();
+        for i in members {
+            let p = &points[i * stride..];
+            let octant = (0..3)
+                .filter(|&k| p[k] >= min[k] + half)
+                .map(|k| 1 << k)
+                .sum::<usize>();
+            octants[octant].push(i);
+        }
+        for (octant, members) in octants.into_iter().enumerate() {
+            if members.is_empty() {
+                continue;
+            }
+            let mut child_min = min;
+            for (k, m) in child_min.iter_mut().enumerate() {
+                if octant & (1 << k) != 0 {
+                    *m += half;
+                }
+            }
+            let child = self.build(points, members, child_min, half, depth + 1);
+            self.cells[index].children.push(child);
+        }
+        index
+    }
+
+    /// Adds the repulsion acting on point `i` to `displacement`, which holds
+    /// one value per active dimension.
+    pub(crate) fn accumulate(
+        &self,
+        points: &[f32],
+        i: usize,
+        dims: usize,
+        params: &LayoutParams,
+        displacement: &mut [f32],
+    ) {
+        if !self.cells.is_empty() {
+            self.accumulate_cell(0, points, i, dims, params, displacement);
+        }
+    }
+
+    fn accumulate_cell(
+        &self,
+        cell: usize,
+        points: &[f32],
+        i: usize,
+        dims: usize,
+        params: &LayoutParams,
+        displacement: &mut [f32],
+    ) {
+        let stride = self.stride;
+        let cell = &self.cells[cell];
+        let p = &points[i * stride..(i + 1) * stride];
+
+        // The distance over all dims is at least the distance in 3D, so cells
+        // beyond the repelling distance in 3D can be skipped exactly.
+        let mut outside_squared = 0.0;
+        for (&min, &x) in cell.min.iter().zip(p) {
+            let d = (min - x).max(x - min - cell.size);
+            if d > 0.0 {
+                outside_squared += d * d;
+            }
+        }
+        if outside_squared >= params.node_repelling_distance * params.node_repelling_distance {
+            return;
+        }
+
+        if cell.children.is_empty() {
+            for &j in &cell.members {
+                if j != i {
+                    let q = &points[j * stride..(j + 1) * stride];
+                    repel(p, q, (i, j), self.masses[j], dims, params, displacement);
+                }
+            }
+            return;
+        }
+
+        let center_distance = distance(p, &cell.center, 3);
+        if outside_squared > 0.0 && cell.size < params.theta * center_distance {
+            // The center is not a point of its own, so it has no index
+            repel(
+                p,
+                &cell.center,
+                (i, usize::MAX),
+                cell.mass,
+                dims,
+                params,
+                displacement,
+            );
+            return;
+        }
+
+        for &child in &cell.children {
+            self.accumulate_cell(child, points, i, dims, params, displacement);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn brute_force(
+        points: &[f32],
+        stride: usize,
+        dims: usize,
+        masses: &[f32],
+        params: &LayoutParams,
+    ) -> Vec<f32> {
+        let num_points = points.len() / stride;
+        let mut out = vec![0.0; num_points * dims];
+        for i in 0..num_points {
+            for j in 0..num_points {
+                if i != j {
+                    repel(
+                        &points[i * stride..(i + 1) * stride],
+                        &points[j * stride..(j + 1) * stride],
+                        (i, j),
+                        masses[j],
+                        dims,
+                        params,
+                        &mut out[i * dims..(i + 1) * dims],
+                    );
+                }
+            }
+        }
+        out
+    }
+
+    fn tree(
+        points: &[f32],
+        stride: usize,
+        dims: usize,
+        masses: &[f32],
+        params: &LayoutParams,
+    ) -> Vec<f32> {
+        let num_points = points.len() / stride;
+        let tree = Octree::new(points, stride, masses);
+        let mut out = vec![0.0; num_points * dims];
+        for i in 0..num_points {
+            tree.accumulate(points, i, dims, params, &mut out[i * dims..(i + 1) * dims]);
+        }
+        out
+    }
+
+    #[test]
+    fn barnes_hut_matches_exact_test() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let stride = 5;
+        let dims = 4;
+        let points: Vec<f32> = (0..500 * stride)
+            .map(|_| rng.gen_range(-5.0..5.0))
+            .collect();
+        let masses: Vec<f32> = (0..500).map(|_| rng.gen_range(1.0..4.0)).collect();
+
+        let exact_params = LayoutParams {
+            theta: 0.0,
+            ..Default::default()
+        };
+        let expected = brute_force(&points, stride, dims, &masses, &exact_params);
+        let actual = tree(&points, stride, dims, &masses, &exact_params);
+        for (a, b) in actual.iter().zip(&expected) {
+            assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+        }
+
+        let approx_params = LayoutParams::default();
+        let actual = tree(&points, stride, dims, &masses, &approx_params);
+        let error: f32 = actual
+            .iter()
+            .zip(&expected)
+            .map(|(a, b)| (a - b).abs())
+            .sum();
+        let magnitude: f32 = expected.iter().map(|v| v.abs()).sum();
+        assert!(error < 0.25 * magnitude, "{} vs {}", error, magnitude);
+    }
+}
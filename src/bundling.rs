@@ -0,0 +1,270 @@
+//! Force-directed edge bundling, which bends edges that run alongside each
+//! other into shared curves so that dense graphs read as a few bundles rather
+//! than a tangle of lines.
+//!
+//! Each edge is a polyline whose inner points are held in place by springs
+//! along the edge and attracted to the matching points of compatible edges,
+//! those of similar direction, length and position. The polylines are
+//! subdivided more finely in each cycle.
+
+/// Settings of [`bundle_edges`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleParams {
+    /// Number of subdivision cycles. The first has 2 segments per edge and
+    /// each one after it twice as many as the one before. With 0 the edges
+    /// stay straight.
+    pub cycles: usize,
+    /// Number of iterations in the first cycle. Each cycle after it has 2/3
+    /// as many as the one before.
+    pub iterations: usize,
+    /// Distance points move per iteration in the first cycle, as a fraction
+    /// of the mean edge length. Halved in each cycle after it.
+    pub step_size: f32,
+    /// Strength of the springs that keep each edge straight.
+    pub stiffness: f32,
+    /// Edges attract each other if their compatibility, from 0 to 1, is at
+    /// least this.
+    pub compatibility_threshold: f32,
+}
+
+impl Default for BundleParams {
+    fn default() -> Self {
+        Self {
+            cycles: 5,
+            iterations: 50,
+            step_size: 0.04,
+            stiffness: 0.1,
+            compatibility_threshold: 0.6,
+        }
+    }
+}
+
+/// Below this length an edge has no direction and is left straight.
+const MIN_LENGTH: f32 = 1e-6;
+
+/// Returns a polyline from the first to the second node of each edge, through
+/// the nodes at `positions`. All polylines have `2^cycles + 1` points. Edges
+/// with no length, such as self-loops, stay straight.
+///
+/// Comparing every pair of edges takes O(e²) time and memory for the pairs
+/// that are compatible.
+pub fn bundle_edges(
+    positions: &[[f32; 3]],
+    edges_indices: &[(usize, usize)],
+    params: &BundleParams,
+) -> Vec<Vec<[f32; 3]>> {
+    let ends = edges_indices
+        .iter()
+        .map(|&(i, j)| (positions[i], positions[j]))
+        .collect::<Vec<_>>();
+    let mut polylines = ends.iter().map(|&(a, b)| vec![a, b]).collect::<Vec<_>>();
+    let lengths = ends
+        .iter()
+        .map(|&(a, b)| distance(a, b))
+        .collect::<Vec<_>>();
+    let straight = lengths.iter().filter(|&&l| l >= MIN_LENGTH).count();
+    let mean_length = lengths.iter().sum::<f32>() / straight.max(1) as f32;
+
+    // Compatible edges, with their compatibility and whether they point the
+    // opposite way
+    let mut compatible = vec![Vec::new(); ends.len()];
+    for a in 0..ends.len() {
+        for b in a + 1..ends.len() {
+            if lengths[a] < MIN_LENGTH || lengths[b] < MIN_LENGTH {
+                continue;
+            }
+            let c = compatibility(ends[a], ends[b]);
+            if c >= params.compatibility_threshold {
+                let reversed = dot(sub(ends[a].1, ends[a].0), sub(ends[b].1, ends[b].0)) < 0.0;
+                compatible[a].push((b, c, reversed));
+                compatible[b].push((a, c, reversed));
+            }
+        }
+    }
+
+    let mut step = params.step_size * mean_length;
+    let mut iterations = params.iterations as f32;
+    for cycle in 0..params.cycles {
+        polylines = polylines.iter().map(|p| subdivide(p)).collect();
+        if cycle > 0 {
+            step *= 0.5;
+            iterations *= 2.0 / 3.0;
+        }
+        for _ in 0..iterations.round() as usize {
+            polylines = (0..polylines.len())
+                .map(|e| {
+                    if lengths[e] < MIN_LENGTH {
+                        return polylines[e].clone();
+                    }
+                    let p = &polylines[e];
+                    let segments = (p.len() - 1) as f32;
+                    let spring = params.stiffness / (lengths[e] * segments);
+                    let mut moved = p.clone();
+                    for i in 1..p.len() - 1 {
+                        let pull = sub(add(p[i - 1], p[i + 1]), scale(p[i], 2.0));
+                        let mut force = scale(pull, spring);
+                        for &(other, c, reversed) in &compatible[e] {
+                            let q = &polylines[other];
+                            let d = sub(q[if reversed { q.len() - 1 - i } else { i }], p[i]);
+                            let length = norm(d);
+                            if length > MIN_LENGTH {
+                                force = add(force, scale(d, c / length));
+                            }
+                        }
+                        moved[i] = add(p[i], scale(force, step));
+                    }
+                    moved
+                })
+                .collect();
+        }
+    }
+    polylines
+}
+
+/// Returns how alike edges `p` and `q` are, from 0 to 1, as the product of
+/// how parallel they are, how similar their lengths are, how close their
+/// midpoints are and how much each one faces the other.
+fn compatibility(p: ([f32; 3], [f32; 3]), q: ([f32; 3], [f32; 3])) -> f32 {
+    let (vp, vq) = (sub(p.1, p.0), sub(q.1, q.0));
+    let (lp, lq) = (norm(vp), norm(vq));
+    let angle = dot(vp, vq).abs() / (lp * lq);
+    let mean = 0.5 * (lp + lq);
+    let scale = 2.0 / (mean / lp.min(lq) + lp.max(lq) / mean);
+    let midpoints = distance(lerp(p.0, p.1, 0.5), lerp(q.0, q.1, 0.5));
+    let position = mean / (mean + midpoints);
+    let visibility = visibility(p, q).min(visibility(q, p));
+    angle * scale * position * visibility
+}
+
+/// Returns how centered the projection of `q` onto the line through `p` is
+/// on `p`, from 0 when it misses the middle of `p` to 1 when centered.
+fn visibility(p: ([f32; 3], [f32; 3]), q: ([f32; 3], [f32; 3])) -> f32 {
+    let v = sub(p.1, p.0);
+    let project = |x: [f32; 3]| add(p.0, scale(v, dot(sub(x, p.0), v) / dot(v, v)));
+    let (i0, i1) = (project(q.0), project(q.1));
+    let width = distance(i0, i1);
+    if width < MIN_LENGTH {
+        return 0.0;
+    }
+    let offset = distance(lerp(p.0, p.1, 0.5), lerp(i0, i1, 0.5));
+    (1.0 - 2.0 * offset / width).max(0.0)
+}
+
+/// Returns `polyline` with a point added halfway along each segment.
+fn subdivide(polyline: &[[f32; 3]]) -> Vec<[f32; 3]> {
+    let mut points = Vec::with_capacity(2 * polyline.len() - 1);
+    for w in polyline.windows(2) {
+        points.push(w[0]);
+        points.push(lerp(w[0], w[1], 0.5));
+    }
+    points.extend(polyline.last());
+    points
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    a.map(|x| x * s)
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn norm(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    norm(sub(b, a))
+}
+
+fn lerp(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    add(a, scale(sub(b, a), t))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bundle_edges_test() {
+        // Two close parallel edges, one of them reversed, and a crossing edge
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [4.0, 0.0, 0.0],
+            [4.0, 0.5, 0.0],
+            [0.0, 0.5, 0.0],
+            [2.0, -2.0, 1.0],
+            [2.0, 2.0, 1.0],
+        ];
+        let edges = [(0, 1), (2, 3), (4, 5), (0, 0)];
+        let params = BundleParams::default();
+        let polylines = bundle_edges(&positions, &edges, &params);
+        assert_eq!(polylines.len(), edges.len());
+        assert!(polylines
+            .iter()
+            .all(|p| p.len() == (1 << params.cycles) + 1));
+        for (p, &(i, j)) in polylines.iter().zip(&edges) {
+            assert_eq!((p[0], p[p.len() - 1]), (positions[i], positions[j]));
+        }
+
+        // The parallel edges are drawn together in the middle
+        let middle = polylines[0].len() / 2;
+        let gap = distance(polylines[0][middle], polylines[1][middle]);
+        assert!(gap < 0.25, "{}", gap);
+
+        // The crossing edge is not compatible with them and stays straight
+        assert!(polylines[2].iter().all(|p| p[0] == 2.0 && p[2] == 1.0));
+        assert!(polylines[3].iter().all(|&p| p == positions[0]));
+    }
+
+    #[test]
+    fn bundle_no_cycles_test() {
+        let positions = [[0.0, 0.0, 0.0], [4.0, 0.0, 0.0], [4.0, 0.5, 0.0]];
+        let edges = [(0, 1), (2, 0), (1, 1)];
+        let params = BundleParams {
+            cycles: 0,
+            ..Default::default()
+        };
+        let polylines = bundle_edges(&positions, &edges, &params);
+        for (p, &(i, j)) in polylines.iter().zip(&edges) {
+            assert_eq!(p, &vec![positions[i], positions[j]]);
+        }
+
+        // Only self-loops, so nothing to bundle
+        let polylines = bundle_edges(&positions, &[(1, 1)], &BundleParams::default());
+        assert_eq!(polylines, vec![vec![positions[1]; 33]]);
+    }
+
+    #[test]
+    fn compatibility_test() {
+        let p = ([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        assert!((compatibility(p, p) - 1.0).abs() < 1e-6);
+        assert!((compatibility(p, (p.1, p.0)) - 1.0).abs() < 1e-6);
+        assert_eq!(compatibility(p, ([0.5, -1.0, 0.0], [0.5, 1.0, 0.0])), 0.0);
+        // Side by side but not overlapping
+        assert_eq!(compatibility(p, ([2.0, 0.0, 0.0], [3.0, 0.0, 0.0])), 0.0);
+    }
+
+    #[test]
+    fn subdivide_test() {
+        let points = subdivide(&[[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [2.0, 2.0, 0.0]]);
+        assert_eq!(
+            points,
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [2.0, 0.0, 0.0],
+                [2.0, 1.0, 0.0],
+                [2.0, 2.0, 0.0]
+            ]
+        );
+    }
+}
@@ -0,0 +1,437 @@
+//! Parsing of DOT color values and generation of stable colors.
+
+/// The X11 color names understood by Graphviz, sorted for binary search.
+const X11_COLORS: &[(&str, [u8; 3])] = &[
+    ("aliceblue", [240, 248, 255]),
+    ("antiquewhite", [250, 235, 215]),
+    ("aqua", [0, 255, 255]),
+    ("aquamarine", [127, 255, 212]),
+    ("azure", [240, 255, 255]),
+    ("beige", [245, 245, 220]),
+    ("bisque", [255, 228, 196]),
+    ("black", [0, 0, 0]),
+    ("blanchedalmond", [255, 235, 205]),
+    ("blue", [0, 0, 255]),
+    ("blueviolet", [138, 43, 226]),
+    ("brown", [165, 42, 42]),
+    ("burlywood", [222, 184, 135]),
+    ("cadetblue", [95, 158, 160]),
+    ("chartreuse", [127, 255, 0]),
+    ("chocolate", [210, 105, 30]),
+    ("coral", [255, 127, 80]),
+    ("cornflowerblue", [100, 149, 237]),
+    ("cornsilk", [255, 248, 220]),
+    ("crimson", [220, 20, 60]),
+    ("cyan", [0, 255, 255]),
+    ("darkblue", [0, 0, 139]),
+    ("darkcyan", [0, 139, 139]),
+    ("darkgoldenrod", [184, 134, 11]),
+    ("darkgray", [169, 169, 169]),
+    ("darkgreen", [0, 100, 0]),
+    ("darkgrey", [169, 169, 169]),
+    ("darkkhaki", [189, 183, 107]),
+    ("darkmagenta", [139, 0, 139]),
+    ("darkolivegreen", [85, 107, 47]),
+    ("darkorange", [255, 140, 0]),
+    ("darkorchid", [153, 50, 204]),
+    ("darkred", [139, 0, 0]),
+    ("darksalmon", [233, 150, 122]),
+    ("darkseagreen", [143, 188, 143]),
+    ("darkslateblue", [72, 61, 139]),
+    ("darkslategray", [47, 79, 79]),
+    ("darkslategrey", [47, 79, 79]),
+    ("darkturquoise", [0, 206, 209]),
+    ("darkviolet", [148, 0, 211]),
+    ("deeppink", [255, 20, 147]),
+    ("deepskyblue", [0, 191, 255]),
+    ("dimgray", [105, 105, 105]),
+    ("dimgrey", [105, 105, 105]),
+    ("dodgerblue", [30, 144, 255]),
+    ("firebrick", [178, 34, 34]),
+    ("floralwhite", [255, 250, 240]),
+    ("forestgreen", [34, 139, 34]),
+    ("fuchsia", [255, 0, 255]),
+    ("gainsboro", [220, 220, 220]),
+    ("ghostwhite", [248, 248, 255]),
+    ("gold", [255, 215, 0]),
+    ("goldenrod", [218, 165, 32]),
+    ("gray", [192, 192, 192]),
+    ("green", [0, 255, 0]),
+    ("greenyellow", [173, 255, 47]),
+    ("grey", [192, 192, 192]),
+    ("honeydew", [240, 255, 240]),
+    ("hotpink", [255, 105, 180]),
+    ("indianred", [205, 92, 92]),
+    ("indigo", [75, 0, 130]),
+    ("ivory", [255, 255, 240]),
+    ("khaki", [240, 230, 140]),
+    ("lavender", [230, 230, 250]),
+    ("lavenderblush", [255, 240, 245]),
+    ("lawngreen", [124, 252, 0]),
+    ("lemonchiffon", [255, 250, 205]),
+    ("lightblue", [173, 216, 230]),
+    ("lightcoral", [240, 128, 128]),
+    ("lightcyan", [224, 255, 255]),
+    ("lightgoldenrod", [238, 221, 130]),
+    ("lightgoldenrodyellow", [250, 250, 210]),
+    ("lightgray", [211, 211, 211]),
+    ("lightgreen", [144, 238, 144]),
+    ("lightgrey", [211, 211, 211]),
+    ("lightpink", [255, 182, 193]),
+    ("lightsalmon", [255, 160, 122]),
+    ("lightseagreen", [32, 178, 170]),
+    ("lightskyblue", [135, 206, 250]),
+    ("lightslateblue", [132, 112, 255]),
+    ("lightslategray", [119, 136, 153]),
+    ("lightslategrey", [119, 136, 153]),
+    ("lightsteelblue", [176, 196, 222]),
+    ("lightyellow", [255, 255, 224]),
+    ("lime", [0, 255, 0]),
+    ("limegreen", [50, 205, 50]),
+    ("linen", [250, 240, 230]),
+    ("magenta", [255, 0, 255]),
+    ("maroon", [176, 48, 96]),
+    ("mediumaquamarine", [102, 205, 170]),
+    ("mediumblue", [0, 0, 205]),
+    ("mediumorchid", [186, 85, 211]),
+    ("mediumpurple", [147, 112, 219]),
+    ("mediumseagreen", [60, 179, 113]),
+    ("mediumslateblue", [123, 104, 238]),
+    ("mediumspringgreen", [0, 250, 154]),
+    ("mediumturquoise", [72, 209, 204]),
+    ("mediumvioletred", [199, 21, 133]),
+    ("midnightblue", [25, 25, 112]),
+    ("mintcream", [245, 255, 250]),
+    ("mistyrose", [255, 228, 225]),
+    ("moccasin", [255, 228, 181]),
+    ("navajowhite", [255, 222, 173]),
+    ("navy", [0, 0, 128]),
+    ("navyblue", [0, 0, 128]),
+    ("oldlace", [253, 245, 230]),
+    ("olive", [128, 128, 0]),
+    ("olivedrab", [107, 142, 35]),
+    ("orange", [255, 165, 0]),
+    ("orangered", [255, 69, 0]),
+    ("orchid", [218, 112, 214]),
+    ("palegoldenrod", [238, 232, 170]),
+    ("palegreen", [152, 251, 152]),
+    ("paleturquoise", [175, 238, 238]),
+    ("palevioletred", [219, 112, 147]),
+    ("papayawhip", [255, 239, 213]),
+    ("peachpuff", [255, 218, 185]),
+    ("peru", [205, 133, 63]),
+    ("pink", [255, 192, 203]),
+    ("plum", [221, 160, 221]),
+    ("powderblue", [176, 224, 230]),
+    ("purple", [160, 32, 240]),
+    ("rebeccapurple", [102, 51, 153]),
+    ("red", [255, 0, 0]),
+    ("rosybrown", [188, 143, 143]),
+    ("royalblue", [65, 105, 225]),
+    ("saddlebrown", [139, 69, 19]),
+    ("salmon", [250, 128, 114]),
+    ("sandybrown", [244, 164, 96]),
+    ("seagreen", [46, 139, 87]),
+    ("seashell", [255, 245, 238]),
+    ("sienna", [160, 82, 45]),
+    ("silver", [192, 192, 192]),
+    ("skyblue", [135, 206, 235]),
+    ("slateblue", [106, 90, 205]),
+    ("slategray", [112, 128, 144]),
+    ("slategrey", [112, 128, 144]),
+    ("snow", [255, 250, 250]),
+    ("springgreen", [0, 255, 127]),
+    ("steelblue", [70, 130, 180]),
+    ("tan", [210, 180, 140]),
+    ("teal", [0, 128, 128]),
+    ("thistle", [216, 191, 216]),
+    ("tomato", [255, 99, 71]),
+    ("turquoise", [64, 224, 208]),
+    ("violet", [238, 130, 238]),
+    ("violetred", [208, 32, 144]),
+    ("wheat", [245, 222, 179]),
+    ("white", [255, 255, 255]),
+    ("whitesmoke", [245, 245, 245]),
+    ("yellow", [255, 255, 0]),
+    ("yellowgreen", [154, 205, 50]),
+];
+
+/// Colors of a Brewer color scheme, stored by how its smaller palettes relate
+/// to the largest one.
+#[derive(Clone, Copy, PartialEq)]
+enum Palette {
+    /// The `n`-color scheme is the first `n` colors.
+    Qualitative(&'static [u32]),
+    /// The ramp is redrawn for each size, so there is a palette for each `n`
+    /// from 3 up.
+    Sequential(&'static [&'static [u32]]),
+}
+
+/// The Brewer schemes understood in `colorscheme` values. A scheme is named
+/// by appending the number of colors, such as `set19` or `blues5`, and each
+/// needs at least 3.
+#[rustfmt::skip]
+const BREWER_SCHEMES: &[(&str, Palette)] = &[
+    ("accent", Palette::Qualitative(&[0x7fc97f, 0xbeaed4, 0xfdc086, 0xffff99, 0x386cb0, 0xf0027f, 0xbf5b17, 0x666666])),
+    ("blues", Palette::Sequential(&[
+        &[0xdeebf7, 0x9ecae1, 0x3182bd],
+        &[0xeff3ff, 0xbdd7e7, 0x6baed6, 0x2171b5],
+        &[0xeff3ff, 0xbdd7e7, 0x6baed6, 0x3182bd, 0x08519c],
+        &[0xeff3ff, 0xc6dbef, 0x9ecae1, 0x6baed6, 0x3182bd, 0x08519c],
+        &[0xeff3ff, 0xc6dbef, 0x9ecae1, 0x6baed6, 0x4292c6, 0x2171b5, 0x084594],
+        &[0xf7fbff, 0xdeebf7, 0xc6dbef, 0x9ecae1, 0x6baed6, 0x4292c6, 0x2171b5, 0x084594],
+        &[0xf7fbff, 0xdeebf7, 0xc6dbef, 0x9ecae1, 0x6baed6, 0x4292c6, 0x2171b5, 0x08519c, 0x08306b],
+    ])),
+    ("dark2", Palette::Qualitative(&[0x1b9e77, 0xd95f02, 0x7570b3, 0xe7298a, 0x66a61e, 0xe6ab02, 0xa6761d, 0x666666])),
+    ("greens", Palette::Sequential(&[
+        &[0xe5f5e0, 0xa1d99b, 0x31a354],
+        &[0xedf8e9, 0xbae4b3, 0x74c476, 0x238b45],
+        &[0xedf8e9, 0xbae4b3, 0x74c476, 0x31a354, 0x006d2c],
+        &[0xedf8e9, 0xc7e9c0, 0xa1d99b, 0x74c476, 0x31a354, 0x006d2c],
+        &[0xedf8e9, 0xc7e9c0, 0xa1d99b, 0x74c476, 0x41ab5d, 0x238b45, 0x005a32],
+        &[0xf7fcf5, 0xe5f5e0, 0xc7e9c0, 0xa1d99b, 0x74c476, 0x41ab5d, 0x238b45, 0x005a32],
+        &[0xf7fcf5, 0xe5f5e0, 0xc7e9c0, 0xa1d99b, 0x74c476, 0x41ab5d, 0x238b45, 0x006d2c, 0x00441b],
+    ])),
+    ("greys", Palette::Sequential(&[
+        &[0xf0f0f0, 0xbdbdbd, 0x636363],
+        &[0xf7f7f7, 0xcccccc, 0x969696, 0x525252],
+        &[0xf7f7f7, 0xcccccc, 0x969696, 0x636363, 0x252525],
+        &[0xf7f7f7, 0xd9d9d9, 0xbdbdbd, 0x969696, 0x636363, 0x252525],
+        &[0xf7f7f7, 0xd9d9d9, 0xbdbdbd, 0x969696, 0x737373, 0x525252, 0x252525],
+        &[0xffffff, 0xf0f0f0, 0xd9d9d9, 0xbdbdbd, 0x969696, 0x737373, 0x525252, 0x252525],
+        &[0xffffff, 0xf0f0f0, 0xd9d9d9, 0xbdbdbd, 0x969696, 0x737373, 0x525252, 0x252525, 0x000000],
+    ])),
+    ("oranges", Palette::Sequential(&[
+        &[0xfee6ce, 0xfdae6b, 0xe6550d],
+        &[0xfeedde, 0xfdbe85, 0xfd8d3c, 0xd94701],
+        &[0xfeedde, 0xfdbe85, 0xfd8d3c, 0xe6550d, 0xa63603],
+        &[0xfeedde, 0xfdd0a2, 0xfdae6b, 0xfd8d3c, 0xe6550d, 0xa63603],
+        &[0xfeedde, 0xfdd0a2, 0xfdae6b, 0xfd8d3c, 0xf16913, 0xd94801, 0x8c2d04],
+        &[0xfff5eb, 0xfee6ce, 0xfdd0a2, 0xfdae6b, 0xfd8d3c, 0xf16913, 0xd94801, 0x8c2d04],
+        &[0xfff5eb, 0xfee6ce, 0xfdd0a2, 0xfdae6b, 0xfd8d3c, 0xf16913, 0xd94801, 0xa63603, 0x7f2704],
+    ])),
+    ("paired", Palette::Qualitative(&[0xa6cee3, 0x1f78b4, 0xb2df8a, 0x33a02c, 0xfb9a99, 0xe31a1c, 0xfdbf6f, 0xff7f00, 0xcab2d6, 0x6a3d9a, 0xffff99, 0xb15928])),
+    ("pastel1", Palette::Qualitative(&[0xfbb4ae, 0xb3cde3, 0xccebc5, 0xdecbe4, 0xfed9a6, 0xffffcc, 0xe5d8bd, 0xfddaec, 0xf2f2f2])),
+    ("pastel2", Palette::Qualitative(&[0xb3e2cd, 0xfdcdac, 0xcbd5e8, 0xf4cae4, 0xe6f5c9, 0xfff2ae, 0xf1e2cc, 0xcccccc])),
+    ("purples", Palette::Sequential(&[
+        &[0xefedf5, 0xbcbddc, 0x756bb1],
+        &[0xf2f0f7, 0xcbc9e2, 0x9e9ac8, 0x6a51a3],
+        &[0xf2f0f7, 0xcbc9e2, 0x9e9ac8, 0x756bb1, 0x54278f],
+        &[0xf2f0f7, 0xdadaeb, 0xbcbddc, 0x9e9ac8, 0x756bb1, 0x54278f],
+        &[0xf2f0f7, 0xdadaeb, 0xbcbddc, 0x9e9ac8, 0x807dba, 0x6a51a3, 0x4a1486],
+        &[0xfcfbfd, 0xefedf5, 0xdadaeb, 0xbcbddc, 0x9e9ac8, 0x807dba, 0x6a51a3, 0x4a1486],
+        &[0xfcfbfd, 0xefedf5, 0xdadaeb, 0xbcbddc, 0x9e9ac8, 0x807dba, 0x6a51a3, 0x54278f, 0x3f007d],
+    ])),
+    ("reds", Palette::Sequential(&[
+        &[0xfee0d2, 0xfc9272, 0xde2d26],
+        &[0xfee5d9, 0xfcae91, 0xfb6a4a, 0xcb181d],
+        &[0xfee5d9, 0xfcae91, 0xfb6a4a, 0xde2d26, 0xa50f15],
+        &[0xfee5d9, 0xfcbba1, 0xfc9272, 0xfb6a4a, 0xde2d26, 0xa50f15],
+        &[0xfee5d9, 0xfcbba1, 0xfc9272, 0xfb6a4a, 0xef3b2c, 0xcb181d, 0x99000d],
+        &[0xfff5f0, 0xfee0d2, 0xfcbba1, 0xfc9272, 0xfb6a4a, 0xef3b2c, 0xcb181d, 0x99000d],
+        &[0xfff5f0, 0xfee0d2, 0xfcbba1, 0xfc9272, 0xfb6a4a, 0xef3b2c, 0xcb181d, 0xa50f15, 0x67000d],
+    ])),
+    ("set1", Palette::Qualitative(&[0xe41a1c, 0x377eb8, 0x4daf4a, 0x984ea3, 0xff7f00, 0xffff33, 0xa65628, 0xf781bf, 0x999999])),
+    ("set2", Palette::Qualitative(&[0x66c2a5, 0xfc8d62, 0x8da0cb, 0xe78ac3, 0xa6d854, 0xffd92f, 0xe5c494, 0xb3b3b3])),
+    ("set3", Palette::Qualitative(&[0x8dd3c7, 0xffffb3, 0xbebada, 0xfb8072, 0x80b1d3, 0xfdb462, 0xb3de69, 0xfccde5, 0xd9d9d9, 0xbc80bd, 0xccebc5, 0xffed6f])),
+];
+
+/// Parses a Graphviz color, either an X11 name or `#rrggbb` / `#rrggbbaa`
+/// hex, into RGBA.
+pub fn parse_color(value: &str) -> Option<[u8; 4]> {
+    parse_color_in(value, None)
+}
+
+/// Like [`parse_color`], but also resolves colors of a Brewer scheme, given
+/// by the `colorscheme` attribute or inline as in `/set19/3`. Colors of a
+/// Brewer scheme are numbered from 1. Names that are not in the scheme are
+/// looked up as X11 colors, as Graphviz does.
+pub fn parse_color_in(value: &str, scheme: Option<&str>) -> Option<[u8; 4]> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+    let name = value.to_lowercase().replace(' ', "");
+    let (scheme, name) = match name.strip_prefix('/') {
+        // `/scheme/color`, where `//color` is the default scheme
+        Some(rest) => {
+            let (scheme, name) = rest.split_once('/')?;
+            (
+                (!scheme.is_empty()).then_some(scheme.to_string()),
+                name.to_string(),
+            )
+        }
+        None => (scheme.map(str::to_lowercase), name),
+    };
+    match scheme.as_deref() {
+        None | Some("x11") => x11_color(&name),
+        Some(scheme) => {
+            let palette = brewer_palette(scheme)?;
+            match name.parse::<usize>() {
+                Ok(index) => {
+                    let [r, g, b] = *palette.get(index.checked_sub(1)?)?;
+                    Some([r, g, b, 255])
+                }
+                Err(_) => x11_color(&name),
+            }
+        }
+    }
+}
+
+fn x11_color(name: &str) -> Option<[u8; 4]> {
+    X11_COLORS
+        .binary_search_by(|(n, _)| (*n).cmp(name))
+        .ok()
+        .map(|i| {
+            let [r, g, b] = X11_COLORS[i].1;
+            [r, g, b, 255]
+        })
+}
+
+/// Returns the colors of a Brewer scheme such as `set19`, or `None` if the
+/// scheme is unknown.
+fn brewer_palette(scheme: &str) -> Option<Vec<[u8; 3]>> {
+    BREWER_SCHEMES.iter().find_map(|&(name, palette)| {
+        let n = scheme.strip_prefix(name)?.parse::<usize>().ok()?;
+        if n < 3 {
+            return None;
+        }
+        let colors = match palette {
+            Palette::Qualitative(colors) => colors.get(..n)?,
+            Palette::Sequential(palettes) => *palettes.get(n - 3)?,
+        };
+        let rgb = |c: u32| [(c >> 16) as u8, (c >> 8) as u8, c as u8];
+        Some(colors.iter().map(|&c| rgb(c)).collect())
+    })
+}
+
+fn parse_hex(hex: &str) -> Option<[u8; 4]> {
+    if !hex.is_ascii() || (hex.len() != 6 && hex.len() != 8) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Some([channel(0)?, channel(2)?, channel(4)?, alpha])
+}
+
+/// Returns a color derived from a hash of `key`, so the same key gets the
+/// same color on every run. Only the hue varies, which keeps all colors
+/// similarly bright.
+pub fn hash_color(key: &str) -> [u8; 3] {
+    // FNV-1a, which unlike the std hasher is stable across Rust releases
+    let hash = key.bytes().fold(0xcbf29ce484222325_u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    let hue = (hash >> 40) as f32 / (1 << 24) as f32;
+    hsv_to_rgb(hue, 0.65, 0.95)
+}
+
+/// Returns color `i` of `n` with hues spread evenly around the color wheel,
+/// as bright as those of [`hash_color`].
+pub fn spread_color(i: usize, n: usize) -> [u8; 3] {
+    hsv_to_rgb(i as f32 / n.max(1) as f32, 0.65, 0.95)
+}
+
+/// Converts a color with all components in `0..=1` to RGB.
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> [u8; 3] {
+    let sector = h.fract() * 6.0;
+    let f = sector.fract();
+    let (p, q, t) = (v * (1.0 - s), v * (1.0 - s * f), v * (1.0 - s * (1.0 - f)));
+    let [r, g, b] = match sector as u32 {
+        0 => [v, t, p],
+        1 => [q, v, p],
+        2 => [p, v, t],
+        3 => [p, q, v],
+        4 => [t, p, v],
+        _ => [v, p, q],
+    };
+    [r, g, b].map(|c| (c * 255.0).round() as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn x11_colors_sorted_test() {
+        assert!(X11_COLORS.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn parse_color_test() {
+        assert_eq!(parse_color("green"), Some([0, 255, 0, 255]));
+        assert_eq!(parse_color("Red"), Some([255, 0, 0, 255]));
+        assert_eq!(parse_color("#1a2B3c"), Some([26, 43, 60, 255]));
+        assert_eq!(parse_color("#1a2b3c80"), Some([26, 43, 60, 128]));
+        assert_eq!(parse_color("#12345"), None);
+        assert_eq!(parse_color("notacolor"), None);
+    }
+
+    #[test]
+    fn color_scheme_test() {
+        assert_eq!(parse_color("/set19/3"), Some([0x4d, 0xaf, 0x4a, 255]));
+        assert_eq!(parse_color("//red"), Some([255, 0, 0, 255]));
+        assert_eq!(parse_color("/x11/red"), Some([255, 0, 0, 255]));
+        assert_eq!(
+            parse_color_in("1", Some("Set13")),
+            Some([0xe4, 0x1a, 0x1c, 255])
+        );
+        assert_eq!(
+            parse_color_in("12", Some("paired12")),
+            Some([0xb1, 0x59, 0x28, 255])
+        );
+        assert_eq!(parse_color_in("red", Some("set13")), Some([255, 0, 0, 255]));
+        // Each size of a sequential scheme has its own palette
+        assert_eq!(
+            brewer_palette("blues3"),
+            Some(vec![
+                [0xde, 0xeb, 0xf7],
+                [0x9e, 0xca, 0xe1],
+                [0x31, 0x82, 0xbd]
+            ])
+        );
+        assert_eq!(
+            parse_color_in("9", Some("blues9")),
+            Some([0x08, 0x30, 0x6b, 255])
+        );
+        assert_eq!(parse_color_in("1", Some("blues10")), None);
+        assert_eq!(parse_color_in("1", Some("blues2")), None);
+        assert_eq!(parse_color_in("4", Some("set13")), None);
+        assert_eq!(parse_color_in("0", Some("set13")), None);
+        assert_eq!(parse_color_in("1", Some("set110")), None);
+        assert_eq!(parse_color_in("1", Some("nosuchscheme")), None);
+        assert_eq!(parse_color("/nosuchscheme/1"), None);
+    }
+
+    #[test]
+    fn brewer_schemes_test() {
+        assert!(BREWER_SCHEMES.windows(2).all(|w| w[0].0 < w[1].0));
+        for (_, palette) in BREWER_SCHEMES {
+            match palette {
+                Palette::Qualitative(colors) => assert!(colors.len() >= 3),
+                Palette::Sequential(palettes) => {
+                    assert!(palettes.iter().enumerate().all(|(i, p)| p.len() == i + 3))
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hash_color_test() {
+        assert_eq!(hash_color("node"), hash_color("node"));
+        assert_ne!(hash_color("node"), hash_color("other"));
+        assert_eq!(hsv_to_rgb(0.0, 1.0, 1.0), [255, 0, 0]);
+        assert_eq!(hsv_to_rgb(2.0 / 3.0, 1.0, 1.0), [0, 0, 255]);
+    }
+
+    #[test]
+    fn spread_color_test() {
+        let colors = (0..5).map(|i| spread_color(i, 5)).collect::<Vec<_>>();
+        assert!(colors
+            .iter()
+            .enumerate()
+            .all(|(i, c)| !colors[..i].contains(c)));
+        assert_eq!(spread_color(0, 5), hsv_to_rgb(0.0, 0.65, 0.95));
+        assert_eq!(spread_color(0, 0), spread_color(0, 1));
+    }
+}
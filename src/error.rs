@@ -0,0 +1,35 @@
+//! Errors reported by the library and the viewer.
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GraphViz3dError {
+    #[error("could not read the graph: {0}")]
+    Io(#[from] std::io::Error),
+    /// An output file or directory could not be written.
+    #[error("could not write {}: {source}", .path.display())]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The input is not valid DOT. Holds the message from the parser.
+    #[error("could not parse the graph: {0}")]
+    Parse(String),
+    /// A config file could not be read or written.
+    #[error("invalid config: {0}")]
+    Config(String),
+    /// Sending the layout to the viewer failed.
+    #[error("could not send the layout to rerun: {0}")]
+    Render(Box<dyn std::error::Error + Send + Sync>),
+    /// A node was referred to by id but is not in the graph.
+    #[error("no node named {0}")]
+    MissingNode(String),
+    /// No path connects the two nodes.
+    #[error("no path from {0} to {1}")]
+    NoPath(String, String),
+    /// The graph has more nodes than the configured limit.
+    #[error("the graph has {nodes} nodes, more than the limit of {max}")]
+    TooManyNodes { nodes: usize, max: usize },
+}
@@ -0,0 +1,292 @@
+//! Writing computed layouts back out.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use graphviz_rust::dot_structures::*;
+use graphviz_rust::printer::{DotPrinter, PrinterContext};
+use serde::Serialize;
+
+use crate::algorithms::degrees;
+use crate::attributes::id_value;
+use crate::metrics::LayoutMetrics;
+use crate::{flatten_graph, node_key, Flattened};
+
+/// Returns `graph` as DOT where every node has a `pos="x,y,z"` attribute taken
+/// from the first three coordinates of its point. `points` holds `stride`
+/// coordinates per node in the order given by [`flatten_graph`]. All other
+/// attributes are kept.
+pub fn export_dot(graph: &Graph, points: &[f32], stride: usize) -> String {
+    let Flattened { nodes, .. } = flatten_graph(graph);
+    let mut positions = HashMap::new();
+    for (node, p) in nodes.iter().zip(points.chunks(stride)) {
+        positions.insert(node_key(&node.id), [p[0], p[1], p[2]]);
+    }
+
+    let mut graph = graph.clone();
+    let stmts = match &mut graph {
+        Graph::Graph { stmts, .. } | Graph::DiGraph { stmts, .. } => stmts,
+    };
+    let mut declared = HashSet::new();
+    set_positions(stmts, &positions, &mut declared);
+
+    // Nodes only referenced by edges need a statement to carry the position
+    for node in &nodes {
+        let key = node_key(&node.id);
+        if !declared.contains(&key) {
+            stmts.push(Stmt::Node(Node {
+                id: node.id.clone(),
+                attributes: vec![pos_attribute(positions[&key])],
+            }));
+        }
+    }
+
+    graph.print(&mut PrinterContext::default())
+}
+
+fn set_positions(
+    stmts: &mut [Stmt],
+    positions: &HashMap<String, [f32; 3]>,
+    declared: &mut HashSet<String>,
+) {
+    for s in stmts {
+        match s {
+            Stmt::Node(node) => {
+                let key = node_key(&node.id);
+                if let Some(&p) = positions.get(&key) {
+                    node.attributes.retain(|a| id_value(&a.0) != "pos");
+                    node.attributes.push(pos_attribute(p));
+                    declared.insert(key);
+                }
+            }
+            Stmt::Subgraph(subgraph) => set_positions(&mut subgraph.stmts, positions, declared),
+            Stmt::Edge(Edge { ty, .. }) => {
+                let vertices = match ty {
+                    EdgeTy::Pair(a, b) => vec![a, b],
+                    EdgeTy::Chain(chain) => chain.iter_mut().collect(),
+                };
+                for v in vertices {
+                    if let Vertex::S(subgraph) = v {
+                        set_positions(&mut subgraph.stmts, positions, declared);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn pos_attribute(p: [f32; 3]) -> Attribute {
+    Attribute(
+        Id::Plain("pos".to_string()),
+        Id::Escaped(format!("\"{},{},{}\"", p[0], p[1], p[2])),
+    )
+}
+
+/// Layout result in a viewer-independent form, for serializing to JSON.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LayoutExport {
+    pub nodes: Vec<NodeExport>,
+    /// Edges as pairs of indices into `nodes`.
+    pub edges: Vec<(usize, usize)>,
+    /// Quality measures of the layout, when they were computed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<LayoutMetrics>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeExport {
+    pub id: String,
+    pub label: String,
+    pub pos: [f32; 3],
+    pub color: [u8; 3],
+    /// Number of edges touching the node.
+    pub degree: usize,
+    /// PageRank of the node, when it was computed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pagerank: Option<f32>,
+}
+
+impl LayoutExport {
+    /// Collects the first three coordinates of each point together with the
+    /// node ids, display labels and colors.
+    pub fn new(
+        nodes: &[Node],
+        edges_indices: &[(usize, usize)],
+        points: &[f32],
+        stride: usize,
+        labels: &[String],
+        colors: &[[u8; 3]],
+    ) -> Self {
+        let degrees = degrees(nodes.len(), edges_indices);
+        let nodes = nodes
+            .iter()
+            .zip(points.chunks(stride))
+            .zip(labels.iter().zip(colors))
+            .zip(degrees)
+            .map(|(((node, p), (label, &color)), degree)| NodeExport {
+                id: id_value(&node.id.0).to_string(),
+                label: label.clone(),
+                pos: [p[0], p[1], p[2]],
+                color,
+                degree,
+                pagerank: None,
+            })
+            .collect();
+        LayoutExport {
+            nodes,
+            edges: edges_indices.to_vec(),
+            metrics: None,
+        }
+    }
+
+    /// Adds the quality measures of the layout.
+    pub fn with_metrics(mut self, metrics: LayoutMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Adds the PageRank of each node, in the order of `nodes`.
+    pub fn with_pagerank(mut self, ranks: &[f32]) -> Self {
+        for (node, &rank) in self.nodes.iter_mut().zip(ranks) {
+            node.pagerank = Some(rank);
+        }
+        self
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("layout export is always serializable")
+    }
+}
+
+/// One step of the solver, for rendering animations outside the viewer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FrameExport {
+    /// Number of solver iterations done when the frame was taken.
+    pub iteration: usize,
+    /// Number of dimensions the solver was working in.
+    pub dims: usize,
+    /// First three coordinates of each node.
+    pub positions: Vec<[f32; 3]>,
+    /// Edges as pairs of indices into `positions`.
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl FrameExport {
+    pub fn new(
+        points: &[f32],
+        stride: usize,
+        dims: usize,
+        iteration: usize,
+        edges_indices: &[(usize, usize)],
+    ) -> Self {
+        FrameExport {
+            iteration,
+            dims,
+            positions: points.chunks(stride).map(|p| [p[0], p[1], p[2]]).collect(),
+            edges: edges_indices.to_vec(),
+        }
+    }
+
+    /// Returns the file name of frame number `n`, such as `frame_0042.json`,
+    /// which sorts in order for up to 10,000 frames.
+    pub fn file_name(n: usize) -> String {
+        format!("frame_{:04}.json", n)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("frame export is always serializable")
+    }
+}
+
+/// Returns the layout as an ASCII PLY mesh, with a colored vertex at the first
+/// three coordinates of each point and an edge element per edge.
+pub fn export_ply(
+    points: &[f32],
+    stride: usize,
+    colors: &[[u8; 3]],
+    edges_indices: &[(usize, usize)],
+) -> String {
+    let mut ply = String::new();
+    writeln!(ply, "ply\nformat ascii 1.0").unwrap();
+    writeln!(ply, "element vertex {}", points.len() / stride).unwrap();
+    for property in ["float x", "float y", "float z"] {
+        writeln!(ply, "property {}", property).unwrap();
+    }
+    for property in ["uchar red", "uchar green", "uchar blue"] {
+        writeln!(ply, "property {}", property).unwrap();
+    }
+    writeln!(ply, "element edge {}", edges_indices.len()).unwrap();
+    writeln!(
+        ply,
+        "property int vertex1\nproperty int vertex2\nend_header"
+    )
+    .unwrap();
+    for (p, [r, g, b]) in points.chunks(stride).zip(colors) {
+        writeln!(ply, "{} {} {} {} {} {}", p[0], p[1], p[2], r, g, b).unwrap();
+    }
+    for (i, j) in edges_indices {
+        writeln!(ply, "{} {}", i, j).unwrap();
+    }
+    ply
+}
+
+/// Returns the layout as a Wavefront OBJ with a vertex at the first three
+/// coordinates of each point and a line per edge. OBJ has no vertex colors.
+pub fn export_obj(points: &[f32], stride: usize, edges_indices: &[(usize, usize)]) -> String {
+    let mut obj = String::new();
+    for p in points.chunks(stride) {
+        writeln!(obj, "v {} {} {}", p[0], p[1], p[2]).unwrap();
+    }
+    // OBJ indices start at 1
+    for (i, j) in edges_indices {
+        writeln!(obj, "l {} {}", i + 1, j + 1).unwrap();
+    }
+    obj
+}
+
+#[cfg(test)]
+mod test {
+    use graphviz_rust::dot_generator::*;
+
+    use super::*;
+
+    #[test]
+    fn mesh_export_test() {
+        let points = [0.0, 1.0, 2.0, 9.0, 3.0, 4.5, 5.0, 9.0];
+        let edges = [(0, 1)];
+        let ply = export_ply(&points, 4, &[[255, 0, 0], [0, 0, 255]], &edges);
+        assert!(ply.starts_with("ply\nformat ascii 1.0\nelement vertex 2\n"));
+        assert!(ply.ends_with("end_header\n0 1 2 255 0 0\n3 4.5 5 0 0 255\n0 1\n"));
+        assert_eq!(
+            export_obj(&points, 4, &edges),
+            "v 0 1 2\nv 3 4.5 5\nl 1 2\n"
+        );
+    }
+
+    #[test]
+    fn frame_export_test() {
+        let points = [0.0, 1.0, 2.0, 9.0, 3.0, 4.5, 5.0, 9.0];
+        let frame = FrameExport::new(&points, 4, 4, 12, &[(0, 1)]);
+        assert_eq!(frame.positions, vec![[0.0, 1.0, 2.0], [3.0, 4.5, 5.0]]);
+        assert_eq!(
+            frame.to_json(),
+            r#"{"iteration":12,"dims":4,"positions":[[0.0,1.0,2.0],[3.0,4.5,5.0]],"edges":[[0,1]]}"#
+        );
+        assert_eq!(FrameExport::file_name(7), "frame_0007.json");
+    }
+
+    #[test]
+    fn layout_export_metrics_test() {
+        let nodes = [node!("a"), node!("b")];
+        let points = [0.0, 0.0, 0.0, 3.0, 4.0, 0.0];
+        let edges = [(0, 1)];
+        let labels = ["a".to_string(), "b".to_string()];
+        let export = LayoutExport::new(&nodes, &edges, &points, 3, &labels, &[[0; 3]; 2]);
+        assert!(!export.to_json().contains("metrics"));
+        let metrics = crate::metrics::layout_metrics(&points, 3, &edges);
+        let export = export.with_metrics(metrics);
+        assert_eq!(export.metrics.unwrap().mean_edge_length, 5.0);
+        assert!(export.to_json().contains("\"mean_edge_length\": 5.0"));
+    }
+}
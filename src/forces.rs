@@ -0,0 +1,262 @@
+//! Displacement passes of the solver.
+//!
+//! Each pass only reads the points and returns a buffer of displacements, which
+//! lets the work be split across threads when the `parallel` feature is on.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::barnes_hut::Octree;
+use crate::LayoutParams;
+
+/// Returns the repulsion displacement of every point, `dims` values per point.
+/// Each point repels the others in proportion to its entry in `masses`.
+pub(crate) fn repulsion_displacements(
+    points: &[f32],
+    stride: usize,
+    dims: usize,
+    masses: &[f32],
+    params: &LayoutParams,
+) -> Vec<f32> {
+    let num_points = points.len() / stride;
+    let mut displacements = vec![0.0; num_points * dims];
+    let tree = if params.exact_repulsion {
+        None
+    } else {
+        Some(Octree::new(points, stride, masses))
+    };
+    let accumulate = |(i, displacement): (usize, &mut [f32])| match &tree {
+        Some(tree) => tree.accumulate(points, i, dims, params, displacement),
+        None => {
+            let p = &points[i * stride..(i + 1) * stride];
+            for (j, q) in points.chunks(stride).enumerate() {
+                if j != i {
+                    repel(p, q, (i, j), masses[j], dims, params, displacement);
+                }
+            }
+        }
+    };
+
+    #[cfg(feature = "parallel")]
+    displacements
+        .par_chunks_mut(dims)
+        .enumerate()
+        .for_each(accumulate);
+    #[cfg(not(feature = "parallel"))]
+    displacements
+        .chunks_mut(dims)
+        .enumerate()
+        .for_each(accumulate);
+
+    displacements
+}
+
+/// Returns the displacement of every point that moves each edge towards its
+/// entry in `rest_lengths`, `dims` values per point. Each edge pulls in
+/// proportion to its weight.
+pub(crate) fn edge_displacements(
+    points: &[f32],
+    stride: usize,
+    dims: usize,
+    edges_indices: &[(usize, usize)],
+    weights: &[f32],
+    rest_lengths: &[f32],
+    params: &LayoutParams,
+) -> Vec<f32> {
+    let correction = |(&(i, j), (&weight, &rest_length)): (&(usize, usize), (&f32, &f32))| {
+        let p1 = &points[i * stride..(i + 1) * stride];
+        let p2 = &points[j * stride..(j + 1) * stride];
+        let length = distance(p1, p2, dims);
+        if length < MIN_DISTANCE {
+            let (axis, sign) = jitter(i, j, dims);
+            let mut u = vec![0.0; dims];
+            u[axis] = sign * weight * params.edge_strength * (rest_length - MIN_DISTANCE) * 0.5;
+            return u;
+        }
+        let c = length - rest_length;
+        let d = weight * params.edge_strength * c * -0.5 / length;
+        (0..dims).map(|k| (p2[k] - p1[k]) * d).collect::<Vec<f32>>()
+    };
+
+    #[cfg(feature = "parallel")]
+    let corrections: Vec<_> = edges_indices
+        .par_iter()
+        .zip(weights.par_iter().zip(rest_lengths))
+        .map(correction)
+        .collect();
+    #[cfg(not(feature = "parallel"))]
+    let corrections: Vec<_> = edges_indices
+        .iter()
+        .zip(weights.iter().zip(rest_lengths))
+        .map(correction)
+        .collect();
+
+    let mut displacements = vec![0.0; points.len() / stride * dims];
+    for (&(i, j), u) in edges_indices.iter().zip(&corrections) {
+        for k in 0..dims {
+            displacements[i * dims + k] -= u[k];
+            displacements[j * dims + k] += u[k];
+        }
+    }
+    displacements
+}
+
+/// Returns the Fruchterman-Reingold displacement of every point, `dims` values
+/// per point, with ideal distance `k`. All pairs of points repel with `k²/d`
+/// and edges attract with `d²/k`, scaled by their weight.
+pub(crate) fn fruchterman_reingold_displacements(
+    points: &[f32],
+    stride: usize,
+    dims: usize,
+    edges_indices: &[(usize, usize)],
+    weights: &[f32],
+    k: f32,
+) -> Vec<f32> {
+    let num_points = points.len() / stride;
+    let mut displacements = vec![0.0; num_points * dims];
+    let accumulate = |(i, displacement): (usize, &mut [f32])| {
+        let p = &points[i * stride..(i + 1) * stride];
+        for (j, q) in points.chunks(stride).enumerate() {
+            if j == i {
+                continue;
+            }
+            let length = distance(p, q, dims);
+            if length < MIN_DISTANCE {
+                let (axis, sign) = jitter(i, j, dims);
+                displacement[axis] -= sign * k * k / MIN_DISTANCE;
+                continue;
+            }
+            // Unit direction times k²/d
+            let f = k * k / (length * length);
+            for (d, (a, b)) in displacement.iter_mut().zip(p.iter().zip(q)).take(dims) {
+                *d += (a - b) * f;
+            }
+        }
+    };
+
+    #[cfg(feature = "parallel")]
+    displacements
+        .par_chunks_mut(dims)
+        .enumerate()
+        .for_each(accumulate);
+    #[cfg(not(feature = "parallel"))]
+    displacements
+        .chunks_mut(dims)
+        .enumerate()
+        .for_each(accumulate);
+
+    for (&(i, j), &weight) in edges_indices.iter().zip(weights) {
+        let p1 = &points[i * stride..(i + 1) * stride];
+        let p2 = &points[j * stride..(j + 1) * stride];
+        // Unit direction times d²/k
+        let f = weight * distance(p1, p2, dims) / k;
+        for axis in 0..dims {
+            let u = (p2[axis] - p1[axis]) * f;
+            displacements[i * dims + axis] += u;
+            displacements[j * dims + axis] -= u;
+        }
+    }
+    displacements
+}
+
+/// Returns the displacement of every point to the position that minimizes its
+/// stress against the graph-theoretic `distances`, `dims` values per point.
+/// Pairs are weighted by the inverse square of their distance and unreachable
+/// pairs are ignored.
+pub(crate) fn stress_displacements(
+    points: &[f32],
+    stride: usize,
+    dims: usize,
+    distances: &[f32],
+) -> Vec<f32> {
+    let num_points = points.len() / stride;
+    let mut displacements = vec![0.0; num_points * dims];
+    let accumulate = |(i, displacement): (usize, &mut [f32])| {
+        let p = &points[i * stride..(i + 1) * stride];
+        let mut total_weight = 0.0;
+        for (j, q) in points.chunks(stride).enumerate() {
+            let d = distances[i * num_points + j];
+            if j == i || !d.is_finite() || d <= 0.0 {
+                continue;
+            }
+            let w = 1.0 / (d * d);
+            let length = distance(p, q, dims);
+            if length < MIN_DISTANCE {
+                let (axis, sign) = jitter(i, j, dims);
+                for k in 0..dims {
+                    displacement[k] += w * q[k];
+                }
+                displacement[axis] -= w * sign * d;
+            } else {
+                for k in 0..dims {
+                    displacement[k] += w * (q[k] + d * (p[k] - q[k]) / length);
+                }
+            }
+            total_weight += w;
+        }
+        for k in 0..dims {
+            displacement[k] = if total_weight > 0.0 {
+                displacement[k] / total_weight - p[k]
+            } else {
+                0.0
+            };
+        }
+    };
+
+    #[cfg(feature = "parallel")]
+    displacements
+        .par_chunks_mut(dims)
+        .enumerate()
+        .for_each(accumulate);
+    #[cfg(not(feature = "parallel"))]
+    displacements
+        .chunks_mut(dims)
+        .enumerate()
+        .for_each(accumulate);
+
+    displacements
+}
+
+/// Points closer than this are treated as coincident.
+const MIN_DISTANCE: f32 = 0.001;
+
+/// Returns the axis and sign of the direction from point `i` to point `j` used
+/// when they coincide. Swapping `i` and `j` flips the sign, so the two points
+/// are pushed apart instead of together.
+fn jitter(i: usize, j: usize, dims: usize) -> (usize, f32) {
+    ((i ^ j) % dims, if i < j { 1.0 } else { -1.0 })
+}
+
+/// Adds the displacement of point `pair.0` at `p` away from point `pair.1` at
+/// `q`, scaled by `mass`.
+pub(crate) fn repel(
+    p: &[f32],
+    q: &[f32],
+    pair: (usize, usize),
+    mass: f32,
+    dims: usize,
+    params: &LayoutParams,
+    out: &mut [f32],
+) {
+    let length = distance(p, q, dims);
+    if length < MIN_DISTANCE {
+        let (axis, sign) = jitter(pair.0, pair.1, dims);
+        let c = params.node_repelling_distance - MIN_DISTANCE;
+        out[axis] -= sign * mass * c.min(params.node_repelling_strength) * 0.5;
+    } else if length < params.node_repelling_distance {
+        let c = params.node_repelling_distance - length;
+        let d = mass * c.min(params.node_repelling_strength) * 0.5 / length;
+        for k in 0..dims {
+            out[k] -= (q[k] - p[k]) * d;
+        }
+    }
+}
+
+pub(crate) fn distance(p: &[f32], q: &[f32], dims: usize) -> f32 {
+    let mut length_squared: f32 = 0.0;
+    for k in 0..dims {
+        let dk = q[k] - p[k];
+        length_squared += dk * dk;
+    }
+    length_squared.sqrt()
+}
@@ -0,0 +1,77 @@
+//! Synthetic graphs of a chosen size, for benchmarks and tests.
+
+use graphviz_rust::dot_structures::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Returns an undirected tree of `num_nodes` nodes where each node after the
+/// first hangs off a random earlier node.
+pub fn random_tree(num_nodes: usize, seed: u64) -> Graph {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let edges = (1..num_nodes)
+        .map(|i| (rng.gen_range(0..i), i))
+        .collect::<Vec<_>>();
+    graph(num_nodes, &edges)
+}
+
+/// Returns an undirected `width` by `height` grid, with each node connected to
+/// its right and lower neighbors.
+pub fn grid(width: usize, height: usize) -> Graph {
+    let mut edges = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            if x + 1 < width {
+                edges.push((i, i + 1));
+            }
+            if y + 1 < height {
+                edges.push((i, i + width));
+            }
+        }
+    }
+    graph(width * height, &edges)
+}
+
+/// Returns a graph with nodes `n0` to `n{num_nodes - 1}`, declared in order,
+/// and `edges` between them.
+fn graph(num_nodes: usize, edges: &[(usize, usize)]) -> Graph {
+    let node_id = |i: usize| NodeId(Id::Plain(format!("n{}", i)), None);
+    let nodes = (0..num_nodes).map(|i| {
+        Stmt::Node(Node {
+            id: node_id(i),
+            attributes: vec![],
+        })
+    });
+    let edges = edges.iter().map(|&(i, j)| {
+        Stmt::Edge(Edge {
+            ty: EdgeTy::Pair(Vertex::N(node_id(i)), Vertex::N(node_id(j))),
+            attributes: vec![],
+        })
+    });
+    Graph::Graph {
+        id: Id::Anonymous(String::new()),
+        strict: false,
+        stmts: nodes.chain(edges).collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithms::connected_components;
+    use crate::{flatten_graph, Flattened};
+
+    #[test]
+    fn random_tree_test() {
+        let Flattened { nodes, edges, .. } = flatten_graph(&random_tree(50, 1));
+        assert_eq!(nodes.len(), 50);
+        assert_eq!(edges.len(), 49);
+        assert_eq!(connected_components(50, &edges).0, 1);
+    }
+
+    #[test]
+    fn grid_test() {
+        let Flattened { nodes, edges, .. } = flatten_graph(&grid(4, 3));
+        assert_eq!(nodes.len(), 12);
+        assert_eq!(edges.len(), 3 * 3 + 4 * 2);
+    }
+}
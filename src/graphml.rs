@@ -0,0 +1,320 @@
+//! Reading GraphML files into the same graph structures as DOT input.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use graphviz_rust::dot_structures::*;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::GraphViz3dError;
+
+/// Returns true if the input is GraphML rather than DOT, judging by the file
+/// extension or by the root element of the text.
+pub fn is_graphml(path: Option<&Path>, text: &str) -> bool {
+    let by_extension = path
+        .and_then(|p| p.to_str())
+        .map(str::to_ascii_lowercase)
+        .is_some_and(|p| p.ends_with(".graphml") || p.ends_with(".graphml.gz"));
+    by_extension || root_element(text) == Some("graphml")
+}
+
+/// Returns the name of the first element in `text`, skipping the XML
+/// declaration, comments and the doctype. Returns `None` for text that does
+/// not start with an element, such as DOT.
+fn root_element(text: &str) -> Option<&str> {
+    let mut rest = text.trim_start();
+    loop {
+        let skipped = if rest.starts_with("<?") {
+            rest.find("?>").map(|end| end + 2)
+        } else if rest.starts_with("<!--") {
+            rest.find("-->").map(|end| end + 3)
+        } else if rest.starts_with("<!") {
+            rest.find('>').map(|end| end + 1)
+        } else {
+            break;
+        };
+        rest = rest[skipped?..].trim_start();
+    }
+    let name = rest.strip_prefix('<')?;
+    let end = name
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .unwrap_or(name.len());
+    Some(&name[..end])
+}
+
+/// A `<key>` declaration, which names the values of `<data>` elements.
+struct Key {
+    /// Element kind the key applies to: `node`, `edge`, `graph` or `all`.
+    domain: String,
+    /// Attribute name the values are given, from `attr.name` or else the id.
+    name: String,
+    default: Option<String>,
+}
+
+/// A node or edge whose `<data>` is being read.
+enum Element {
+    Node(NodeId, Vec<Attribute>),
+    Edge(NodeId, NodeId, Vec<Attribute>),
+}
+
+/// Parses every top-level `<graph>` in a GraphML document. Each `<data>`
+/// value becomes an attribute named by its key's `attr.name`, so that keys
+/// such as `label`, `color` and `shape` are handled as in DOT. Key defaults
+/// become `node [...]` and `edge [...]` statements. Nodes of nested graphs
+/// are added to the enclosing top-level graph.
+pub fn parse_graphml(text: &str) -> Result<Vec<Graph>, GraphViz3dError> {
+    let mut reader = Reader::from_str(text);
+    reader.trim_text(true);
+
+    let mut keys = HashMap::<String, Key>::new();
+    let mut key_id = None;
+    let mut graphs = Vec::new();
+    let mut graph_depth = 0;
+    let mut graph_id = None;
+    let mut directed = false;
+    let mut graph_attributes = Vec::new();
+    let mut stmts = Vec::new();
+    let mut elements = Vec::<Element>::new();
+    let mut data_key = None;
+    let mut value = String::new();
+    loop {
+        let event = reader.read_event().map_err(xml_error)?;
+        if let Event::Start(e) | Event::Empty(e) = &event {
+            let attrs = xml_attributes(e)?;
+            let get = |name: &str| attrs.get(name).cloned().unwrap_or_default();
+            match e.name().as_ref() {
+                b"key" => {
+                    let id = get("id");
+                    let name = attrs
+                        .get("attr.name")
+                        .cloned()
+                        .unwrap_or_else(|| id.clone());
+                    let domain = attrs.get("for").cloned().unwrap_or_else(|| "all".into());
+                    keys.insert(
+                        id.clone(),
+                        Key {
+                            domain,
+                            name,
+                            default: None,
+                        },
+                    );
+                    key_id = Some(id);
+                }
+                b"graph" => {
+                    graph_depth += 1;
+                    if graph_depth == 1 {
+                        graph_id = attrs.get("id").cloned();
+                        directed = get("edgedefault") == "directed";
+                    }
+                }
+                b"node" => elements.push(Element::Node(NodeId(quoted(&get("id")), None), vec![])),
+                b"edge" => elements.push(Element::Edge(
+                    NodeId(quoted(&get("source")), None),
+                    NodeId(quoted(&get("target")), None),
+                    vec![],
+                )),
+                b"data" => {
+                    data_key = Some(get("key"));
+                    value.clear();
+                }
+                b"default" => value.clear(),
+                _ => (),
+            }
+        }
+        let end = match &event {
+            Event::Text(t) => {
+                value.push_str(&t.unescape().map_err(xml_error)?);
+                None
+            }
+            Event::CData(t) => {
+                value.push_str(&String::from_utf8_lossy(t));
+                None
+            }
+            Event::Empty(e) => Some(e.name().as_ref().to_vec()),
+            Event::End(e) => Some(e.name().as_ref().to_vec()),
+            Event::Eof => break,
+            _ => None,
+        };
+        match end.as_deref() {
+            Some(b"key") => key_id = None,
+            Some(b"default") => {
+                if let Some(key) = key_id.as_ref().and_then(|id| keys.get_mut(id)) {
+                    key.default = Some(value.clone());
+                }
+            }
+            Some(b"data") => {
+                let key = data_key.take().unwrap_or_default();
+                let name = keys.get(&key).map_or(key, |k| k.name.clone());
+                let attribute = Attribute(attribute_name(&name), quoted(&value));
+                match elements.last_mut() {
+                    Some(Element::Node(_, attributes) | Element::Edge(_, _, attributes)) => {
+                        attributes.push(attribute)
+                    }
+                    None if graph_depth == 1 => graph_attributes.push(attribute),
+                    None => (),
+                }
+            }
+            Some(b"node") | Some(b"edge") => {
+                stmts.push(match elements.pop() {
+                    Some(Element::Node(id, attributes)) => Stmt::Node(Node { id, attributes }),
+                    Some(Element::Edge(a, b, attributes)) => Stmt::Edge(Edge {
+                        ty: EdgeTy::Pair(Vertex::N(a), Vertex::N(b)),
+                        attributes,
+                    }),
+                    None => continue,
+                });
+            }
+            Some(b"graph") => {
+                graph_depth -= 1;
+                if graph_depth == 0 {
+                    let mut all = key_defaults(&keys);
+                    all.push(Stmt::GAttribute(GraphAttributes::Graph(std::mem::take(
+                        &mut graph_attributes,
+                    ))));
+                    all.append(&mut stmts);
+                    let id = graph_id
+                        .take()
+                        .map_or_else(|| Id::Anonymous(String::new()), |id| quoted(&id));
+                    graphs.push(if directed {
+                        Graph::DiGraph {
+                            id,
+                            strict: false,
+                            stmts: all,
+                        }
+                    } else {
+                        Graph::Graph {
+                            id,
+                            strict: false,
+                            stmts: all,
+                        }
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+    if graphs.is_empty() {
+        return Err(GraphViz3dError::Parse(
+            "no <graph> element in the GraphML document".to_string(),
+        ));
+    }
+    Ok(graphs)
+}
+
+/// Returns `node [...]` and `edge [...]` statements with the key defaults.
+fn key_defaults(keys: &HashMap<String, Key>) -> Vec<Stmt> {
+    let mut node = Vec::new();
+    let mut edge = Vec::new();
+    let mut sorted = keys.values().collect::<Vec<_>>();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+    for key in sorted {
+        if let Some(default) = &key.default {
+            let attribute = Attribute(attribute_name(&key.name), quoted(default));
+            if matches!(key.domain.as_str(), "node" | "all") {
+                node.push(attribute.clone());
+            }
+            if matches!(key.domain.as_str(), "edge" | "all") {
+                edge.push(attribute);
+            }
+        }
+    }
+    vec![
+        Stmt::GAttribute(GraphAttributes::Node(node)),
+        Stmt::GAttribute(GraphAttributes::Edge(edge)),
+    ]
+}
+
+fn xml_attributes(e: &BytesStart) -> Result<HashMap<String, String>, GraphViz3dError> {
+    e.attributes()
+        .map(|a| {
+            let a = a.map_err(xml_error)?;
+            let key = String::from_utf8_lossy(a.key.as_ref()).into_owned();
+            Ok((key, a.unescape_value().map_err(xml_error)?.into_owned()))
+        })
+        .collect()
+}
+
+fn xml_error(e: impl std::fmt::Display) -> GraphViz3dError {
+    GraphViz3dError::Parse(e.to_string())
+}
+
+/// Returns `name` as a plain DOT id if it is one, so that it matches the
+/// attribute names used in DOT files.
+fn attribute_name(name: &str) -> Id {
+    let plain = name.starts_with(|c: char| !c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        Id::Plain(name.to_string())
+    } else {
+        quoted(name)
+    }
+}
+
+/// Returns `value` as a quoted DOT id.
+fn quoted(value: &str) -> Id {
+    Id::Escaped(format!("\"{}\"", value.replace('"', "\\\"")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::attributes::{attribute_value, id_value};
+    use crate::{flatten_graph, is_directed, Flattened};
+
+    const GRAPHML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="d0" for="node" attr.name="color" attr.type="string">
+    <default>yellow</default>
+  </key>
+  <key id="d1" for="node" attr.name="label" attr.type="string"/>
+  <key id="d2" for="edge" attr.name="weight" attr.type="double"/>
+  <graph id="G" edgedefault="directed">
+    <node id="n0"><data key="d1">first &amp; foremost</data></node>
+    <node id="n1"><data key="d0">green</data></node>
+    <edge source="n0" target="n1"><data key="d2">2.5</data></edge>
+    <edge source="n1" target="n2"/>
+  </graph>
+</graphml>"#;
+
+    #[test]
+    fn is_graphml_test() {
+        assert!(is_graphml(None, GRAPHML));
+        assert!(is_graphml(Some(Path::new("a/b.GraphML")), ""));
+        assert!(!is_graphml(Some(Path::new("b.dot")), "digraph { a -> b }"));
+        assert!(!is_graphml(None, "<!-- note --> <svg></svg>"));
+    }
+
+    #[test]
+    fn parse_graphml_test() {
+        let graphs = parse_graphml(GRAPHML).unwrap();
+        assert_eq!(graphs.len(), 1);
+        assert!(is_directed(&graphs[0]));
+        let Flattened {
+            nodes,
+            edges,
+            edge_attributes,
+        } = flatten_graph(&graphs[0]);
+        let names = nodes.iter().map(|n| id_value(&n.id.0)).collect::<Vec<_>>();
+        assert_eq!(names, vec!["n0", "n1", "n2"]);
+        assert_eq!(edges, vec![(0, 1), (1, 2)]);
+        assert_eq!(
+            attribute_value(&nodes[0].attributes, "label"),
+            Some("first & foremost")
+        );
+        assert_eq!(
+            attribute_value(&nodes[0].attributes, "color"),
+            Some("yellow")
+        );
+        assert_eq!(
+            attribute_value(&nodes[1].attributes, "color"),
+            Some("green")
+        );
+        assert_eq!(attribute_value(&edge_attributes[0], "weight"), Some("2.5"));
+    }
+
+    #[test]
+    fn missing_graph_test() {
+        assert!(parse_graphml("<graphml></graphml>").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! Extraction of display labels from DOT `label` values.
+
+use graphviz_rust::dot_structures::Id;
+
+use crate::attributes::id_value;
+
+/// Values substituted for the escapes that Graphviz expands in labels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LabelEscapes<'a> {
+    /// Replaces `\N`, the id of the node being labeled. Empty for edges and
+    /// graphs.
+    pub node: &'a str,
+    /// Replaces `\G`, the id of the graph.
+    pub graph: &'a str,
+}
+
+/// Returns the display text of a `label` value, with the escapes in
+/// `escapes` expanded. With `basename`, only the part after the last `/` is
+/// kept, which shortens labels that are file paths. HTML-like labels are
+/// reduced to their text content instead.
+pub fn label_text(label: &Id, basename: bool, escapes: &LabelEscapes) -> String {
+    if let Id::Html(html) = label {
+        return html_text(html);
+    }
+    let text = expand_escapes(id_value(label), escapes);
+    if basename {
+        match text.rfind('/') {
+            Some(i) => text[i + 1..].to_string(),
+            None => text,
+        }
+    } else {
+        text
+    }
+}
+
+/// Replaces `\N` and `\G` in `text` and drops `\L`, which stands for the
+/// label of the object and so, as in Graphviz, expands to nothing within the
+/// label itself. Other escapes, including `\\`, are kept as they are.
+pub fn expand_escapes(text: &str, escapes: &LabelEscapes) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('N') => expanded.push_str(escapes.node),
+            Some('G') => expanded.push_str(escapes.graph),
+            Some('L') => (),
+            Some(c) => {
+                expanded.push('\\');
+                expanded.push(c);
+            }
+            None => expanded.push('\\'),
+        }
+    }
+    expanded
+}
+
+/// Strips the tags from an HTML-like label and decodes the basic entities.
+/// Line breaks become newlines so multi-line labels stay readable.
+fn html_text(html: &str) -> String {
+    let html = strip_wrapper(html);
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('>')
+            .map_or(rest.len(), |end| start + end + 1);
+        let tag = rest[start + 1..end].trim_end_matches('>').trim();
+        if tag.to_ascii_lowercase().starts_with("br") {
+            text.push('\n');
+        }
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+/// Removes the `<` and `>` that delimit an HTML-like label, if they enclose
+/// the whole value rather than being the first and last tags.
+fn strip_wrapper(html: &str) -> &str {
+    let mut depth = 0;
+    for (i, c) in html.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            _ => (),
+        }
+        if depth == 0 {
+            return if i + 1 == html.len() && i > 0 {
+                &html[1..i]
+            } else {
+                html
+            };
+        }
+    }
+    html
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plain_label_test() {
+        let id = Id::Plain("my/path/node".to_string());
+        assert_eq!(
+            label_text(&id, false, &LabelEscapes::default()),
+            "my/path/node"
+        );
+        assert_eq!(label_text(&id, true, &LabelEscapes::default()), "node");
+    }
+
+    #[test]
+    fn escaped_label_test() {
+        let id = Id::Escaped("\"src/main.rs\"".to_string());
+        assert_eq!(
+            label_text(&id, false, &LabelEscapes::default()),
+            "src/main.rs"
+        );
+        assert_eq!(label_text(&id, true, &LabelEscapes::default()), "main.rs");
+        let id = Id::Escaped("\"a \\\"quoted\\\" word\"".to_string());
+        assert_eq!(
+            label_text(&id, false, &LabelEscapes::default()),
+            "a \\\"quoted\\\" word"
+        );
+    }
+
+    #[test]
+    fn html_label_test() {
+        let id = Id::Html("<b>bold</b>".to_string());
+        assert_eq!(label_text(&id, false, &LabelEscapes::default()), "bold");
+        let id = Id::Html("<<b>a/b</b> &amp; <i>c &lt; d</i>>".to_string());
+        assert_eq!(
+            label_text(&id, true, &LabelEscapes::default()),
+            "a/b & c < d"
+        );
+        let id = Id::Html("<line one<br/>line two>".to_string());
+        assert_eq!(
+            label_text(&id, false, &LabelEscapes::default()),
+            "line one\nline two"
+        );
+    }
+
+    #[test]
+    fn label_escapes_test() {
+        let escapes = LabelEscapes {
+            node: "a",
+            graph: "G",
+        };
+        let id = Id::Escaped("\"\\N in \\G\\L\"".to_string());
+        assert_eq!(label_text(&id, false, &escapes), "a in G");
+        let id = Id::Escaped("\"dir/\\N\"".to_string());
+        assert_eq!(label_text(&id, true, &escapes), "a");
+        // Other escapes and escaped backslashes are kept
+        assert_eq!(expand_escapes("\\\\N\\n\\", &escapes), "\\\\N\\n\\");
+        let id = Id::Html("<\\N>".to_string());
+        assert_eq!(label_text(&id, false, &escapes), "\\N");
+    }
+}
@@ -0,0 +1,2438 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use flate2::read::GzDecoder;
+
+use graphviz_rust::dot_structures::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+pub use crate::error::GraphViz3dError;
+
+use crate::attributes::{
+    attribute_value, graph_attributes, id_value, is_pinned, parse_pos, parse_weight, RankDir,
+};
+use crate::label::{label_text, LabelEscapes};
+
+pub mod algorithms;
+pub mod attributes;
+mod barnes_hut;
+pub mod bundling;
+pub mod color;
+pub mod error;
+pub mod export;
+mod forces;
+pub mod generators;
+pub mod graphml;
+pub mod label;
+pub mod metrics;
+pub mod shapes;
+
+/// Minimum time between progress reports with [`LayoutParams::verbose`].
+const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Default number of coordinates stored per point, see [`LayoutParams::max_dims`].
+pub const MAX_DIMS: usize = 10;
+
+pub fn id_to_string(id: &Id) -> String {
+    match id {
+        Id::Html(ref v) => format!("html {}", v),
+        Id::Escaped(ref v) => format!("esc {}", v),
+        Id::Plain(ref v) => format!("plain {}", v),
+        Id::Anonymous(ref v) => format!("anon {}", v),
+    }
+}
+
+pub fn port_to_string(port: &Port) -> String {
+    match port {
+        Port(None, None) => "".to_string(),
+        Port(Some(ref id), None) => id_to_string(id),
+        Port(None, Some(ref dir)) => format!(":{}", dir),
+        Port(Some(ref id), Some(ref dir)) => format!("{}:{}", id_to_string(id), dir),
+    }
+}
+
+pub fn node_id_to_string(node_id: &NodeId) -> String {
+    match node_id.1 {
+        None => id_to_string(&node_id.0),
+        Some(ref port) => format!("{}:{}", id_to_string(&node_id.0), port_to_string(port)),
+    }
+}
+
+/// Returns the key identifying the node `node_id` refers to. The port is
+/// ignored, so `a:p1` and `a:p2` are both the node `a`.
+pub fn node_key(node_id: &NodeId) -> String {
+    id_to_string(&node_id.0)
+}
+
+/// Force model used by the solver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutAlgorithm {
+    /// Capped short-range repulsion, edge springs and a hierarchy force that
+    /// lifts edge sources above their targets.
+    #[default]
+    Legacy,
+    /// Fruchterman-Reingold with attraction `d²/k` along edges and repulsion
+    /// `k²/d` between all nodes, where `k = sqrt(fr_area / n)`.
+    FruchtermanReingold,
+    /// Stress majorization, which places nodes so that their distances match
+    /// the shortest path distances in the graph. Edges are their rest length
+    /// divided by their weight long. Needs O(n²) memory.
+    StressMajorization,
+}
+
+/// Above this many nodes the distances for stress majorization take enough
+/// memory to be worth a warning. See [`GraphDistances::memory_size`].
+pub const STRESS_WARN_NODES: usize = 5000;
+
+/// Tuning knobs for the force-directed solver.
+///
+/// Override individual values with struct update syntax:
+///
+/// ```
+/// let params = graphviz3d::LayoutParams {
+///     edge_length: 2.0,
+///     ..Default::default()
+/// };
+/// # assert_eq!(params.edge_length, 2.0);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutParams {
+    /// Force model to solve with.
+    pub algorithm: LayoutAlgorithm,
+    /// Area of the Fruchterman-Reingold frame, which sets its ideal distance.
+    pub fr_area: f32,
+    /// Fraction of the length error corrected per iteration for each edge.
+    pub edge_strength: f32,
+    /// Rest length of the edge springs, for edges without a `len` attribute.
+    pub edge_length: f32,
+    /// Maximum distance two nodes are pushed apart per iteration.
+    pub node_repelling_strength: f32,
+    /// Nodes closer than this repel each other.
+    pub node_repelling_distance: f32,
+    /// Distance edge sources are lifted, and targets lowered, per iteration
+    /// while they are level. The force grows with how far the pair is from
+    /// `float_distance` apart.
+    pub float_strength: f32,
+    /// Vertical separation beyond which the float force stops acting.
+    pub float_distance: f32,
+    /// Factor the float force is multiplied by for edges on a cycle, whose
+    /// nodes cannot all be above each other.
+    pub float_cycle_damping: f32,
+    /// Fraction of the distance to their common rank that nodes in
+    /// `rank=same` subgraphs move per iteration.
+    pub same_rank_strength: f32,
+    /// Pull of each node towards a height equal to its number of incoming edges.
+    pub node_degree_strength: f32,
+    /// Fraction of its distance to the origin each node moves towards it per
+    /// iteration, or 0 to disable.
+    pub gravity: f32,
+    /// Factor all displacements are scaled by in the first iteration.
+    pub initial_temperature: f32,
+    /// Factor the temperature is multiplied by after each iteration.
+    pub cooling_rate: f32,
+    /// The temperature never decays below this.
+    pub min_temperature: f32,
+    /// Maximum distance a node moves in a single repulsion or edge step.
+    pub max_displacement: f32,
+    /// Maximum distance a node moves in one iteration, all forces together.
+    pub max_step: f32,
+    /// Scales the repulsion of each node by `(degree + 1)^exponent`, so that
+    /// with a positive exponent hubs push their neighbors farther away. 0
+    /// repels equally from every node.
+    pub degree_repulsion_exponent: f32,
+    /// Use the exact O(n²) repulsion instead of the Barnes-Hut approximation.
+    pub exact_repulsion: bool,
+    /// Barnes-Hut opening threshold. Cells smaller than `theta` times their
+    /// distance are treated as a single mass; 0 makes the tree exact.
+    pub theta: f32,
+    /// Number of batches of iterations at each dimension level. The progress
+    /// callback runs after each batch, so this sets how often a viewer
+    /// updates.
+    pub outer_iterations: usize,
+    /// Number of iterations in each batch, which sets how far the forces are
+    /// resolved between updates.
+    pub inner_iterations: usize,
+    /// Stop iterating at a dimension level once the total squared displacement
+    /// of an iteration falls below this.
+    pub convergence_threshold: f32,
+    /// Seed for the initial positions, or `None` for a different layout each run.
+    pub seed: Option<u64>,
+    /// Nodes start at random coordinates between `-init_radius` and
+    /// `init_radius`, except for the height.
+    pub init_radius: f32,
+    /// Multiply `init_radius` by the square root of the number of nodes, so
+    /// that large graphs do not start out packed densely and take many
+    /// iterations to spread.
+    pub scale_init_radius: bool,
+    /// Axis the float force orients edges along, from source to target, or
+    /// `None` to disable it together with the `rank=same` pull. With a
+    /// `rankdir` of `LR` or `RL` the graph is laid out along a horizontal
+    /// axis instead, see [`RankDir::axis_and_sign`].
+    pub hierarchy_axis: Option<usize>,
+    /// Print the progress of the solve to stderr a few times per second.
+    pub verbose: bool,
+    /// Initialize only x and y from `pos` attributes, rescaled to
+    /// `edge_length`, and anneal the remaining dimensions from random values.
+    /// Without it, `pos` values are copied as they are.
+    pub warm_start_2d: bool,
+    /// Radius of the nodes with the lowest degree when sizing by degree.
+    pub min_radius: f32,
+    /// Radius of the nodes with the highest degree when sizing by degree.
+    pub max_radius: f32,
+    /// Radius of nodes per inch of their `width` or `height`, whichever is
+    /// larger. The default gives nodes of the Graphviz default width of 0.75
+    /// inches the default radius.
+    pub radius_per_inch: f32,
+    /// Number of dimensions the solve stops at. Above 3, the final layout is
+    /// shown by its first three coordinates, or projected with
+    /// `pca_projection`.
+    pub final_dims: usize,
+    /// Project a solve that stops above 3D onto its three principal axes
+    /// instead of dropping the extra coordinates. Pinned nodes move too.
+    pub pca_projection: bool,
+    /// Solve in the XY plane only, without annealing from higher dimensions.
+    /// The hierarchy force then acts along y unless `hierarchy_axis` is 0,
+    /// in place of the pull of nodes with many edges towards y+, and z stays
+    /// 0.
+    pub flat: bool,
+    /// Once solved, move the centroid to the origin and scale the layout
+    /// uniformly so that its largest extent is this size. Pinned nodes move
+    /// too. `None` keeps the coordinates of the solver.
+    pub normalize_size: Option<f32>,
+    /// Number of coordinates stored per point, at least 3. The solve starts
+    /// in `max_dims - 1` dimensions and anneals down to 3.
+    pub max_dims: usize,
+}
+
+impl Default for LayoutParams {
+    fn default() -> Self {
+        Self {
+            algorithm: LayoutAlgorithm::Legacy,
+            fr_area: 100.0,
+            edge_strength: 0.1,
+            edge_length: 1.0,
+            node_repelling_strength: 0.1,
+            node_repelling_distance: 2.0,
+            float_strength: 0.02,
+            float_distance: 2.0,
+            float_cycle_damping: 0.2,
+            same_rank_strength: 0.1,
+            node_degree_strength: 0.01,
+            gravity: 0.0,
+            initial_temperature: 1.0,
+            cooling_rate: 0.997,
+            min_temperature: 0.05,
+            max_displacement: 1.0,
+            max_step: 1.0,
+            degree_repulsion_exponent: 0.0,
+            exact_repulsion: false,
+            theta: 0.5,
+            outer_iterations: 10,
+            inner_iterations: 10,
+            convergence_threshold: 1e-6,
+            seed: None,
+            init_radius: 1.0,
+            scale_init_radius: false,
+            hierarchy_axis: Some(2),
+            verbose: false,
+            warm_start_2d: false,
+            min_radius: 0.03,
+            max_radius: 0.15,
+            radius_per_inch: 0.05 / 0.75,
+            final_dims: 3,
+            pca_projection: false,
+            flat: false,
+            normalize_size: None,
+            max_dims: MAX_DIMS,
+        }
+    }
+}
+
+impl LayoutParams {
+    /// Returns the number of dimensions the solve stops at, which is
+    /// `final_dims` limited to what `max_dims` leaves room for.
+    pub fn active_dims(&self) -> usize {
+        self.final_dims.clamp(3, self.max_dims.max(4) - 1)
+    }
+}
+
+/// Returns the text of a DOT file, decompressing it first if it is gzipped.
+pub fn decode_dot(bytes: Vec<u8>) -> Result<String, GraphViz3dError> {
+    let bytes = if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(bytes.as_slice()).read_to_end(&mut decompressed)?;
+        decompressed
+    } else {
+        bytes
+    };
+    String::from_utf8(bytes)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e).into())
+}
+
+/// Parses the DOT source of a graph. Lines starting with `#`, such as the
+/// `#line` directives left by the C preprocessor, are ignored.
+pub fn parse_graph(dot: &str) -> Result<Graph, GraphViz3dError> {
+    parse_graph_at(dot, 0)
+}
+
+/// Parses every graph in `dot`, which may hold several graph definitions
+/// one after the other.
+pub fn parse_graphs(dot: &str) -> Result<Vec<Graph>, GraphViz3dError> {
+    split_graphs(dot)
+        .into_iter()
+        .map(|graph| {
+            // Report errors at their line in the whole input
+            let offset = graph.as_ptr() as usize - dot.as_ptr() as usize;
+            parse_graph_at(graph, dot[..offset].matches('\n').count())
+        })
+        .collect()
+}
+
+/// Parses `dot`, which starts after `lines_before` lines of the input.
+fn parse_graph_at(dot: &str, lines_before: usize) -> Result<Graph, GraphViz3dError> {
+    // Blank out rather than remove the lines, to keep the line numbers
+    let stripped = dot
+        .lines()
+        .map(|line| {
+            if line.trim_start().starts_with('#') {
+                ""
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    graphviz_rust::parse(&stripped).map_err(|e| parse_error(dot, &e, lines_before))
+}
+
+/// Rewrites a parser error to name the line and column it occurred at and the
+/// text of that line. Errors without a position are passed on unchanged.
+fn parse_error(dot: &str, message: &str, lines_before: usize) -> GraphViz3dError {
+    let position = message.lines().find_map(|line| {
+        let (line, column) = line.trim().strip_prefix("-->")?.trim().split_once(':')?;
+        Some((line.parse::<usize>().ok()?, column.parse::<usize>().ok()?))
+    });
+    let Some((line, column)) = position else {
+        return GraphViz3dError::Parse(message.to_string());
+    };
+    let expected = message
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("= "))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let text = dot.lines().nth(line.saturating_sub(1)).unwrap_or("").trim();
+    GraphViz3dError::Parse(format!(
+        "line {}, column {}: {} near `{}`",
+        lines_before + line,
+        column,
+        expected,
+        text
+    ))
+}
+
+/// Splits DOT source into its graph definitions by matching the braces of
+/// each graph body, skipping strings, HTML ids and comments. Returns the
+/// whole input if it has no complete graph, so the parser reports the error.
+fn split_graphs(dot: &str) -> Vec<&str> {
+    let bytes = dot.as_bytes();
+    let mut graphs = Vec::new();
+    let (mut start, mut braces, mut angles) = (0, 0, 0);
+    let mut i = 0;
+    let mut line_start = true;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let rest = &bytes[i..];
+        if angles > 0 {
+            match c {
+                b'<' => angles += 1,
+                b'>' => angles -= 1,
+                _ => (),
+            }
+        } else if c == b'"' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != b'"' {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+        } else if rest.starts_with(b"//") || (c == b'#' && line_start) {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        } else if rest.starts_with(b"/*") {
+            i += rest
+                .windows(2)
+                .skip(2)
+                .position(|w| w == b"*/")
+                .map_or(rest.len(), |p| p + 4);
+            continue;
+        } else {
+            match c {
+                b'<' => angles += 1,
+                b'{' => braces += 1,
+                b'}' => {
+                    braces -= 1;
+                    if braces == 0 {
+                        graphs.push(&dot[start..=i]);
+                        start = i + 1;
+                    }
+                }
+                _ => (),
+            }
+        }
+        line_start = c == b'\n' || (line_start && c.is_ascii_whitespace());
+        i += 1;
+    }
+    if graphs.is_empty() {
+        graphs.push(dot);
+    }
+    graphs
+}
+
+/// Returns true for `digraph` inputs, whose edges point from tail to head.
+pub fn is_directed(graph: &Graph) -> bool {
+    matches!(graph, Graph::DiGraph { .. })
+}
+
+fn graph_statements(graph: &Graph) -> &Vec<Stmt> {
+    match graph {
+        Graph::Graph {
+            id: _,
+            strict: _,
+            ref stmts,
+        }
+        | Graph::DiGraph {
+            id: _,
+            strict: _,
+            ref stmts,
+        } => stmts,
+    }
+}
+
+/// Returns the id of `graph`, or `None` if it is anonymous.
+pub fn graph_id(graph: &Graph) -> Option<String> {
+    let (Graph::Graph { id, .. } | Graph::DiGraph { id, .. }) = graph;
+    match id {
+        Id::Anonymous(_) => None,
+        id => Some(id_value(id).to_string()).filter(|id| !id.is_empty()),
+    }
+}
+
+/// Returns the display text of the graph-level `label` of `graph`.
+pub fn graph_label(graph: &Graph) -> Option<String> {
+    let id = graph_id(graph).unwrap_or_default();
+    let escapes = LabelEscapes {
+        graph: &id,
+        ..Default::default()
+    };
+    graph_attributes(graph_statements(graph))
+        .iter()
+        .rev()
+        .find(|a| id_value(&a.0) == "label")
+        .map(|a| label_text(&a.1, false, &escapes))
+}
+
+/// The nodes and edges of a graph with subgraphs, chains and defaults
+/// resolved, still in their parsed form. See [`FlatGraph`] for ids and labels
+/// as plain strings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flattened {
+    /// Nodes in order of first appearance.
+    pub nodes: Vec<Node>,
+    /// Edges as pairs of indices into `nodes`.
+    pub edges: Vec<(usize, usize)>,
+    /// Attributes of each edge, in the order of `edges`.
+    pub edge_attributes: Vec<Vec<Attribute>>,
+}
+
+/// Flattens `graph` into its nodes and edges.
+pub fn flatten_graph(graph: &Graph) -> Flattened {
+    let mut nodes = Vec::<Node>::new();
+    let mut node_indices = HashMap::<String, usize>::new();
+    let mut edges = Vec::<(NodeId, NodeId, Vec<Attribute>)>::new();
+    collect_statements(
+        graph_statements(graph),
+        &Defaults::default(),
+        &mut nodes,
+        &mut node_indices,
+        &mut edges,
+    );
+
+    if let Graph::Graph { strict: true, .. } | Graph::DiGraph { strict: true, .. } = graph {
+        edges = dedup_edges(edges, is_directed(graph));
+    }
+
+    // Every endpoint was declared while collecting, so this only looks up
+    let mut edges_indices = Vec::with_capacity(edges.len());
+    let mut edge_attributes = Vec::with_capacity(edges.len());
+    for (a, b, attributes) in edges {
+        edges_indices.push((
+            insert_node(&mut nodes, &mut node_indices, &a),
+            insert_node(&mut nodes, &mut node_indices, &b),
+        ));
+        edge_attributes.push(attributes);
+    }
+    Flattened {
+        nodes,
+        edges: edges_indices,
+        edge_attributes,
+    }
+}
+
+/// A node of a [`FlatGraph`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeInfo {
+    /// Node id without its port or quotes.
+    pub id: String,
+    /// Display text of the `label` attribute, if the node has one.
+    pub label: Option<String>,
+    /// Attributes of the node, including those inherited from `node [...]`.
+    pub attributes: Vec<Attribute>,
+}
+
+/// An edge of a [`FlatGraph`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeInfo {
+    /// Indices into `nodes` of the tail and head of the edge.
+    pub indices: (usize, usize),
+    /// Display text of the `label` attribute, if the edge has one.
+    pub label: Option<String>,
+    /// Attributes of the edge, including those inherited from `edge [...]`.
+    pub attributes: Vec<Attribute>,
+}
+
+/// The nodes and edges of a graph with subgraphs, chains and defaults
+/// resolved, as produced by [`flatten_graph`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatGraph {
+    /// Nodes in order of first appearance, which is the order of the points
+    /// in a [`Layout`].
+    pub nodes: Vec<NodeInfo>,
+    pub edges: Vec<EdgeInfo>,
+}
+
+impl FlatGraph {
+    pub fn new(graph: &Graph) -> Self {
+        let Flattened {
+            nodes,
+            edges: edges_indices,
+            edge_attributes,
+        } = flatten_graph(graph);
+        let graph_id = graph_id(graph).unwrap_or_default();
+        let label = |attributes: &[Attribute], node: &str| {
+            let escapes = LabelEscapes {
+                node,
+                graph: &graph_id,
+            };
+            attributes
+                .iter()
+                .rev()
+                .find(|a| id_value(&a.0) == "label")
+                .map(|a| label_text(&a.1, false, &escapes))
+        };
+        let nodes = nodes
+            .into_iter()
+            .map(|node| {
+                let id = id_value(&node.id.0).to_string();
+                NodeInfo {
+                    label: label(&node.attributes, &id),
+                    id,
+                    attributes: node.attributes,
+                }
+            })
+            .collect();
+        let edges = edges_indices
+            .into_iter()
+            .zip(edge_attributes)
+            .map(|(indices, attributes)| EdgeInfo {
+                indices,
+                label: label(&attributes, ""),
+                attributes,
+            })
+            .collect();
+        Self { nodes, edges }
+    }
+
+    /// Returns the index pairs of the edges, as taken by the functions that
+    /// work on [`flatten_graph`] output.
+    pub fn edges_indices(&self) -> Vec<(usize, usize)> {
+        self.edges.iter().map(|e| e.indices).collect()
+    }
+}
+
+/// Collapses edges between the same pair of nodes into the first one, merging
+/// their attributes, as Graphviz does for strict graphs. In undirected graphs
+/// `a -- b` and `b -- a` are the same edge.
+fn dedup_edges(
+    edges: Vec<(NodeId, NodeId, Vec<Attribute>)>,
+    directed: bool,
+) -> Vec<(NodeId, NodeId, Vec<Attribute>)> {
+    let mut first_index = HashMap::<(String, String), usize>::new();
+    let mut deduped = Vec::<(NodeId, NodeId, Vec<Attribute>)>::new();
+    for (a, b, attributes) in edges {
+        let (key_a, key_b) = (node_key(&a), node_key(&b));
+        // Undirected edges are keyed by their sorted ends
+        let key = if directed || key_a <= key_b {
+            (key_a, key_b)
+        } else {
+            (key_b, key_a)
+        };
+        match first_index.get(&key) {
+            Some(&i) => deduped[i].2.extend(attributes),
+            None => {
+                first_index.insert(key, deduped.len());
+                deduped.push((a, b, attributes));
+            }
+        }
+    }
+    deduped
+}
+
+/// Returns `graph` reduced to the `max_nodes` nodes with the most edges and
+/// the edges among them. Ties keep the nodes that appear first. Edge chains
+/// through a dropped node are split around it, and all other statements are
+/// kept.
+pub fn keep_highest_degree(graph: &Graph, max_nodes: usize) -> Graph {
+    let Flattened {
+        nodes,
+        edges: edges_indices,
+        ..
+    } = flatten_graph(graph);
+    let degrees = algorithms::degrees(nodes.len(), &edges_indices);
+    let mut order = (0..nodes.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| std::cmp::Reverse(degrees[i]));
+    let kept = order
+        .iter()
+        .take(max_nodes)
+        .map(|&i| node_key(&nodes[i].id))
+        .collect::<HashSet<_>>();
+    retain_graph_nodes(graph, &kept)
+}
+
+/// Returns `graph` reduced to the nodes that have every `key=value` pair of
+/// `filters` among their attributes, and the edges among them. Attributes set
+/// by `node [...]` defaults count.
+pub fn filter_nodes(graph: &Graph, filters: &[(String, String)]) -> Graph {
+    let Flattened { nodes, .. } = flatten_graph(graph);
+    let kept = nodes
+        .iter()
+        .filter(|node| {
+            filters
+                .iter()
+                .all(|(key, value)| attribute_value(&node.attributes, key) == Some(value.as_str()))
+        })
+        .map(|node| node_key(&node.id))
+        .collect::<HashSet<_>>();
+    retain_graph_nodes(graph, &kept)
+}
+
+/// Returns `graph` with only the nodes whose keys are in `kept`, and the edges
+/// among them.
+fn retain_graph_nodes(graph: &Graph, kept: &HashSet<String>) -> Graph {
+    let mut graph = graph.clone();
+    let stmts = match &mut graph {
+        Graph::Graph { stmts, .. } | Graph::DiGraph { stmts, .. } => stmts,
+    };
+    retain_nodes(stmts, kept);
+    graph
+}
+
+fn retain_nodes(stmts: &mut Vec<Stmt>, kept: &HashSet<String>) {
+    let mut retained = Vec::with_capacity(stmts.len());
+    for mut s in stmts.drain(..) {
+        match &mut s {
+            Stmt::Node(n) if !kept.contains(&node_key(&n.id)) => continue,
+            Stmt::Subgraph(subgraph) => retain_nodes(&mut subgraph.stmts, kept),
+            Stmt::Edge(Edge { ty, attributes }) => {
+                let vertices = match ty {
+                    EdgeTy::Pair(a, b) => vec![a.clone(), b.clone()],
+                    EdgeTy::Chain(chain) => chain.clone(),
+                };
+                let mut run = Vec::new();
+                for mut v in vertices {
+                    let keep = match &mut v {
+                        Vertex::N(id) => kept.contains(&node_key(id)),
+                        Vertex::S(subgraph) => {
+                            retain_nodes(&mut subgraph.stmts, kept);
+                            true
+                        }
+                    };
+                    if keep {
+                        run.push(v);
+                    } else {
+                        push_chain(&mut retained, std::mem::take(&mut run), attributes);
+                    }
+                }
+                push_chain(&mut retained, run, attributes);
+                continue;
+            }
+            _ => (),
+        }
+        retained.push(s);
+    }
+    *stmts = retained;
+}
+
+/// Adds the part of an edge chain that is left after dropping nodes. A single
+/// vertex becomes a node or subgraph statement so that it is still declared.
+fn push_chain(stmts: &mut Vec<Stmt>, mut run: Vec<Vertex>, attributes: &[Attribute]) {
+    let ty = match run.len() {
+        0 => return,
+        1 => {
+            stmts.push(match run.remove(0) {
+                Vertex::N(id) => Stmt::Node(Node {
+                    id: NodeId(id.0, None),
+                    attributes: vec![],
+                }),
+                Vertex::S(subgraph) => Stmt::Subgraph(subgraph),
+            });
+            return;
+        }
+        2 => {
+            let b = run.remove(1);
+            EdgeTy::Pair(run.remove(0), b)
+        }
+        _ => EdgeTy::Chain(run),
+    };
+    stmts.push(Stmt::Edge(Edge {
+        ty,
+        attributes: attributes.to_vec(),
+    }));
+}
+
+/// Returns the groups of nodes declared in `{ rank=same; ... }` subgraphs, as
+/// indices into `nodes` from [`flatten_graph`].
+pub fn same_rank_groups(graph: &Graph, nodes: &[Node]) -> Vec<Vec<usize>> {
+    let node_indices = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node_key(&node.id), i))
+        .collect::<HashMap<_, _>>();
+    let mut groups = Vec::new();
+    collect_same_rank(graph_statements(graph), &node_indices, &mut groups);
+    groups
+}
+
+fn collect_same_rank(
+    stmts: &[Stmt],
+    node_indices: &HashMap<String, usize>,
+    groups: &mut Vec<Vec<usize>>,
+) {
+    for s in stmts {
+        if let Stmt::Subgraph(subgraph) = s {
+            let rank =
+                attribute_value(&graph_attributes(&subgraph.stmts), "rank").map(str::to_string);
+            if rank.as_deref() == Some("same") {
+                let group = subgraph_nodes(subgraph)
+                    .iter()
+                    .filter_map(|id| node_indices.get(&node_key(id)).copied())
+                    .collect::<Vec<_>>();
+                if group.len() > 1 {
+                    groups.push(group);
+                }
+            }
+            collect_same_rank(&subgraph.stmts, node_indices, groups);
+        }
+    }
+}
+
+/// A `subgraph cluster_*` block, which is drawn as a region enclosing its nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    /// Subgraph id, such as `cluster_x`.
+    pub name: String,
+    /// Attributes set inside the cluster, such as `label` and `color`.
+    pub attributes: Vec<Attribute>,
+    /// Indices into `nodes` from [`flatten_graph`] of every node in the
+    /// cluster, including those in nested clusters.
+    pub nodes: Vec<usize>,
+    /// Index of the innermost cluster containing this one.
+    pub parent: Option<usize>,
+}
+
+/// Returns every cluster in `graph`, parents before their nested clusters.
+pub fn clusters(graph: &Graph, nodes: &[Node]) -> Vec<Cluster> {
+    let node_indices = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node_key(&node.id), i))
+        .collect::<HashMap<_, _>>();
+    let mut clusters = Vec::new();
+    collect_clusters(graph_statements(graph), None, &node_indices, &mut clusters);
+    clusters
+}
+
+fn collect_clusters(
+    stmts: &[Stmt],
+    parent: Option<usize>,
+    node_indices: &HashMap<String, usize>,
+    clusters: &mut Vec<Cluster>,
+) {
+    for s in stmts {
+        if let Stmt::Subgraph(subgraph) = s {
+            let name = id_value(&subgraph.id).to_string();
+            let mut inner_parent = parent;
+            // Subgraphs are not keyed by id anywhere else, and nodes by their
+            // own ids, so anonymous subgraphs cannot collide. They are never
+            // clusters, whatever id the parser makes up for them.
+            let anonymous = matches!(subgraph.id, Id::Anonymous(_));
+            if !anonymous && name.starts_with("cluster") {
+                inner_parent = Some(clusters.len());
+                clusters.push(Cluster {
+                    name,
+                    attributes: graph_attributes(&subgraph.stmts),
+                    nodes: subgraph_nodes(subgraph)
+                        .iter()
+                        .filter_map(|id| node_indices.get(&node_key(id)).copied())
+                        .collect(),
+                    parent,
+                });
+            }
+            collect_clusters(&subgraph.stmts, inner_parent, node_indices, clusters);
+        }
+    }
+}
+
+/// Returns the index of the node `id`, adding it without attributes if new.
+/// Nodes are stored without the port.
+fn insert_node(
+    nodes: &mut Vec<Node>,
+    node_indices: &mut HashMap<String, usize>,
+    id: &NodeId,
+) -> usize {
+    *node_indices.entry(node_key(id)).or_insert_with(|| {
+        nodes.push(Node {
+            id: NodeId(id.0.clone(), None),
+            attributes: vec![],
+        });
+        nodes.len() - 1
+    })
+}
+
+/// Attributes from `node [...]` and `edge [...]` statements, which apply to
+/// nodes and edges declared after them in the same graph or subgraph.
+#[derive(Clone, Default)]
+struct Defaults {
+    node: Vec<Attribute>,
+    edge: Vec<Attribute>,
+}
+
+/// Returns the index of the node `id`, adding it with the current default
+/// attributes if new.
+fn declare_node(
+    nodes: &mut Vec<Node>,
+    node_indices: &mut HashMap<String, usize>,
+    id: &NodeId,
+    defaults: &Defaults,
+) -> usize {
+    let count = nodes.len();
+    let i = insert_node(nodes, node_indices, id);
+    if i == count {
+        nodes[i].attributes = defaults.node.clone();
+    }
+    i
+}
+
+/// Flattens nodes and edges from `stmts` and all nested subgraphs. Default
+/// attributes are put before the node's or edge's own, so that the latter
+/// take precedence.
+fn collect_statements(
+    stmts: &[Stmt],
+    defaults: &Defaults,
+    nodes: &mut Vec<Node>,
+    node_indices: &mut HashMap<String, usize>,
+    edges: &mut Vec<(NodeId, NodeId, Vec<Attribute>)>,
+) {
+    // Defaults set in a subgraph do not leak out of it
+    let mut defaults = defaults.clone();
+    for s in stmts {
+        match s {
+            Stmt::GAttribute(GraphAttributes::Node(list)) => {
+                defaults.node.extend(list.iter().cloned())
+            }
+            Stmt::GAttribute(GraphAttributes::Edge(list)) => {
+                defaults.edge.extend(list.iter().cloned())
+            }
+            Stmt::Node(n) => {
+                let i = declare_node(nodes, node_indices, &n.id, &defaults);
+                nodes[i].attributes.extend(n.attributes.iter().cloned());
+            }
+            Stmt::Subgraph(subgraph) => {
+                collect_statements(&subgraph.stmts, &defaults, nodes, node_indices, edges)
+            }
+            Stmt::Edge(Edge { ty, attributes }) => {
+                let vertices = edge_vertices(ty);
+                for v in &vertices {
+                    match v {
+                        Vertex::N(id) => {
+                            declare_node(nodes, node_indices, id, &defaults);
+                        }
+                        Vertex::S(subgraph) => collect_statements(
+                            &subgraph.stmts,
+                            &defaults,
+                            nodes,
+                            node_indices,
+                            edges,
+                        ),
+                    }
+                }
+                let mut attributes_with_defaults = defaults.edge.clone();
+                attributes_with_defaults.extend(attributes.iter().cloned());
+                // An edge to or from a subgraph connects to every node in it
+                for pair in vertices.windows(2) {
+                    for a in vertex_nodes(pair[0]) {
+                        for b in vertex_nodes(pair[1]) {
+                            let mut attributes = port_attributes(&a, &b);
+                            attributes.extend(attributes_with_defaults.iter().cloned());
+                            edges.push((a.clone(), b, attributes));
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Returns the ports of an edge's endpoints as `tailport` and `headport`
+/// attributes, which is what `a:p1 -> b:p2` is shorthand for.
+fn port_attributes(a: &NodeId, b: &NodeId) -> Vec<Attribute> {
+    [("tailport", a), ("headport", b)]
+        .into_iter()
+        .filter_map(|(key, id)| {
+            let Port(name, compass) = id.1.as_ref()?;
+            let value = match (name, compass) {
+                (Some(name), Some(compass)) => format!("{}:{}", id_value(name), compass),
+                (Some(name), None) => id_value(name).to_string(),
+                (None, Some(compass)) => compass.clone(),
+                (None, None) => return None,
+            };
+            Some(Attribute(
+                Id::Plain(key.to_string()),
+                Id::Escaped(format!("\"{}\"", value)),
+            ))
+        })
+        .collect()
+}
+
+/// Returns the endpoints of an edge statement in order, for pairs and chains
+/// alike.
+fn edge_vertices(ty: &EdgeTy) -> Vec<&Vertex> {
+    match ty {
+        EdgeTy::Pair(a, b) => vec![a, b],
+        EdgeTy::Chain(chain) => chain.iter().collect(),
+    }
+}
+
+/// Returns the nodes an edge endpoint refers to, expanding subgraphs.
+fn vertex_nodes(vertex: &Vertex) -> Vec<NodeId> {
+    match vertex {
+        Vertex::N(id) => vec![id.clone()],
+        Vertex::S(subgraph) => subgraph_nodes(subgraph),
+    }
+}
+
+/// Returns every node mentioned in `subgraph` or any of its nested subgraphs.
+fn subgraph_nodes(subgraph: &Subgraph) -> Vec<NodeId> {
+    let mut ids = Vec::new();
+    for s in &subgraph.stmts {
+        match s {
+            Stmt::Node(n) => ids.push(n.id.clone()),
+            Stmt::Subgraph(nested) => ids.extend(subgraph_nodes(nested)),
+            Stmt::Edge(Edge { ty, .. }) => {
+                for v in edge_vertices(ty) {
+                    ids.extend(vertex_nodes(v));
+                }
+            }
+            _ => (),
+        }
+    }
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(node_key(id)));
+    ids
+}
+
+/// Result of a [`layout`] run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    /// Final positions as a flat buffer with `params.max_dims` coordinates per
+    /// node, in the order given by [`flatten_graph`].
+    pub points: Vec<f32>,
+    /// Number of solver iterations performed over all dimension levels.
+    pub iterations: usize,
+    /// Number of connected components in the graph.
+    pub num_components: usize,
+    /// Connected component of each node.
+    pub components: Vec<usize>,
+}
+
+/// Lays out `graph` with the force-directed solver.
+pub fn layout(graph: &Graph, params: &LayoutParams) -> Layout {
+    layout_with_progress(graph, params, |_, _| {})
+}
+
+/// Like [`layout`], but calls `progress` with the current points and number of
+/// active dimensions after each batch of iterations.
+pub fn layout_with_progress<F>(graph: &Graph, params: &LayoutParams, mut progress: F) -> Layout
+where
+    F: FnMut(&[f32], usize),
+{
+    layout_streaming(graph, params, |points, dims, _| progress(points, dims))
+}
+
+/// Like [`layout_with_progress`], but also passes the number of iterations
+/// done so far, so that a viewer can show the intermediate layouts on a
+/// timeline. The points hold `params.max_dims` coordinates per node, in the
+/// order given by [`flatten_graph`], of which the first `dims` are active.
+pub fn layout_streaming<F>(graph: &Graph, params: &LayoutParams, on_step: F) -> Layout
+where
+    F: FnMut(&[f32], usize, usize),
+{
+    solve(graph, params, None, None, on_step)
+}
+
+/// Like [`layout_streaming`], but starts each node that has an entry in
+/// `start`, keyed by [`node_key`], from that point, so that an edited graph
+/// keeps the arrangement of a previous layout. Only the final dimension level
+/// is solved, starting at `min_temperature`. Nodes without an entry start at
+/// random, and entries for nodes that are no longer in the graph are ignored.
+pub fn relayout_streaming<F>(
+    graph: &Graph,
+    params: &LayoutParams,
+    start: &HashMap<String, Vec<f32>>,
+    on_step: F,
+) -> Layout
+where
+    F: FnMut(&[f32], usize, usize),
+{
+    solve(graph, params, None, Some(start), on_step)
+}
+
+/// Like [`layout`], but stress majorization uses the precomputed `distances`
+/// of `graph` instead of computing them again.
+pub fn layout_with_distances(
+    graph: &Graph,
+    params: &LayoutParams,
+    distances: &GraphDistances,
+) -> Layout {
+    solve(graph, params, Some(distances), None, |_, _, _| {})
+}
+
+/// Shortest path distances between all pairs of nodes of a graph, which
+/// stress majorization lays the graph out by. Computing them takes a search
+/// from every node, so callers that lay out the same graph several times can
+/// build them once and pass them to [`layout_with_distances`].
+///
+/// The matrix holds one `f32` per pair of nodes, so it needs `4 * n²` bytes:
+/// 400 MB for 10,000 nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphDistances {
+    num_points: usize,
+    /// Row-major `num_points * num_points` matrix.
+    distances: Vec<f32>,
+}
+
+impl GraphDistances {
+    /// Computes the distances between the nodes of `graph`, in the order
+    /// given by [`flatten_graph`]. Each edge is its rest length divided by its
+    /// weight long, as in the layout.
+    pub fn new(graph: &Graph, params: &LayoutParams) -> Self {
+        let Flattened {
+            nodes,
+            edges: edges_indices,
+            edge_attributes,
+        } = flatten_graph(graph);
+        let (springs, weights, rest_lengths) = springs(&edges_indices, &edge_attributes, params);
+        Self::compute(nodes.len(), &springs, &weights, &rest_lengths)
+    }
+
+    fn compute(
+        num_points: usize,
+        springs: &[(usize, usize)],
+        weights: &[f32],
+        rest_lengths: &[f32],
+    ) -> Self {
+        let lengths = rest_lengths
+            .iter()
+            .zip(weights)
+            .map(|(&length, &w)| length / w)
+            .collect::<Vec<_>>();
+        GraphDistances {
+            num_points,
+            distances: algorithms::all_pairs_distances(num_points, springs, &lengths),
+        }
+    }
+
+    /// Returns the number of bytes the distances between `num_points` nodes
+    /// take. Callers can warn before computing them when `num_points` is above
+    /// [`STRESS_WARN_NODES`].
+    pub fn memory_size(num_points: usize) -> usize {
+        num_points * num_points * std::mem::size_of::<f32>()
+    }
+
+    /// Returns the length of the shortest path between nodes `i` and `j`, or
+    /// `f32::INFINITY` if they are not connected.
+    pub fn distance(&self, i: usize, j: usize) -> f32 {
+        self.distances[i * self.num_points + j]
+    }
+}
+
+/// Returns the edges that exert a force, leaving out self-loops, with their
+/// weights and rest lengths. The rest length is the `len` attribute, or
+/// `params.edge_length` without a valid one.
+fn springs(
+    edges_indices: &[(usize, usize)],
+    edge_attributes: &[Vec<Attribute>],
+    params: &LayoutParams,
+) -> (Vec<(usize, usize)>, Vec<f32>, Vec<f32>) {
+    let mut springs = Vec::with_capacity(edges_indices.len());
+    let mut weights = Vec::with_capacity(edges_indices.len());
+    let mut rest_lengths = Vec::with_capacity(edges_indices.len());
+    for (&(i, j), attributes) in edges_indices.iter().zip(edge_attributes) {
+        if i == j {
+            continue;
+        }
+        springs.push((i, j));
+        weights.push(parse_weight(attribute_value(attributes, "weight")));
+        let len = attribute_value(attributes, "len").and_then(|len| len.parse::<f32>().ok());
+        rest_lengths.push(
+            len.filter(|len| len.is_finite() && *len > 0.0)
+                .unwrap_or(params.edge_length),
+        );
+    }
+    (springs, weights, rest_lengths)
+}
+
+fn solve<F>(
+    graph: &Graph,
+    params: &LayoutParams,
+    distances: Option<&GraphDistances>,
+    start: Option<&HashMap<String, Vec<f32>>>,
+    mut on_step: F,
+) -> Layout
+where
+    F: FnMut(&[f32], usize, usize),
+{
+    let mut rng = make_rng(params.seed);
+
+    let Flattened {
+        nodes,
+        edges: edges_indices,
+        edge_attributes,
+    } = flatten_graph(graph);
+    let num_points = nodes.len();
+    let stride = params.max_dims;
+
+    // Nothing to solve, so place a lone node at the origin
+    if num_points <= 1 {
+        let points = vec![0.0; num_points * stride];
+        on_step(&points, 3, 0);
+        return Layout {
+            points,
+            iterations: 0,
+            num_components: num_points,
+            components: vec![0; num_points],
+        };
+    }
+
+    let mut num_incoming = vec![0; num_points];
+    for &(_, j) in &edges_indices {
+        num_incoming[j] += 1;
+    }
+    let num_incoming = num_incoming;
+
+    // Self-loops exert no force on their node, so leave them out of the solve
+    let (springs, weights, rest_lengths) = springs(&edges_indices, &edge_attributes, params);
+
+    let mut points = initial_points(&num_incoming, params, &mut rng);
+
+    // Start components apart from each other on a grid
+    let (num_components, components) = algorithms::connected_components(num_points, &edges_indices);
+    if num_components > 1 {
+        let mut sizes = vec![0; num_components];
+        for &c in &components {
+            sizes[c] += 1;
+        }
+        let largest = *sizes.iter().max().unwrap() as f32;
+        let spacing = 2.0 * params.node_repelling_distance * largest.cbrt();
+        let columns = (num_components as f32).sqrt().ceil() as usize;
+        let row_axis = if params.flat { 1 } else { 2 };
+        for (p, &c) in points.chunks_mut(stride).zip(&components) {
+            p[0] += spacing * (c % columns) as f32;
+            p[row_axis] += spacing * (c / columns) as f32;
+        }
+    }
+
+    // Start from the position hints in the file where available
+    let positions = nodes
+        .iter()
+        .map(|node| attribute_value(&node.attributes, "pos").and_then(parse_pos))
+        .collect::<Vec<_>>();
+    seed_positions(&mut points, stride, &positions, &springs, params);
+
+    // Pinned nodes stay where the file puts them, in 3D
+    let pinned = nodes
+        .iter()
+        .zip(&positions)
+        .map(|(node, pos)| pos.is_some() && is_pinned(&node.attributes))
+        .collect::<Vec<_>>();
+    for ((p, pos), _) in points
+        .chunks_mut(stride)
+        .zip(&positions)
+        .zip(&pinned)
+        .filter(|(_, &pinned)| pinned)
+    {
+        let seeded = pos.as_ref().map_or(0, Vec::len);
+        p[seeded..].fill(0.0);
+    }
+
+    // Nodes of a previous layout continue from where they were
+    if let Some(start) = start {
+        for ((p, node), _) in points
+            .chunks_mut(stride)
+            .zip(&nodes)
+            .zip(&pinned)
+            .filter(|(_, &pinned)| !pinned)
+        {
+            if let Some(q) = start.get(&node_key(&node.id)) {
+                let n = q.len().min(stride);
+                p[..n].copy_from_slice(&q[..n]);
+                p[n..].fill(0.0);
+            }
+        }
+    }
+
+    // A flat layout keeps everything in the XY plane
+    if params.flat {
+        for p in points.chunks_mut(stride) {
+            p[2..].fill(0.0);
+        }
+    }
+
+    let masses = algorithms::degrees(num_points, &springs)
+        .into_iter()
+        .map(|degree| ((degree + 1) as f32).powf(params.degree_repulsion_exponent))
+        .collect::<Vec<_>>();
+
+    let rankdir = RankDir::parse(attribute_value(
+        &graph_attributes(graph_statements(graph)),
+        "rankdir",
+    ));
+    // In the plane the hierarchy goes along y, as in Graphviz
+    let hierarchy_axis = match params.hierarchy_axis {
+        Some(axis) if params.flat => Some(axis.min(1)),
+        axis => axis,
+    };
+    let rank = hierarchy_axis.map(|axis| rankdir.axis_and_sign(axis));
+    let same_rank = same_rank_groups(graph, &nodes);
+    // Edges within a strongly connected component are on a cycle
+    let (_, cycles) = algorithms::strongly_connected_components(num_points, &springs);
+    let float_factors = springs
+        .iter()
+        .map(|&(i, j)| {
+            if cycles[i] == cycles[j] {
+                params.float_cycle_damping
+            } else {
+                1.0
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let computed;
+    let distances: &[f32] = match (params.algorithm, distances) {
+        (LayoutAlgorithm::StressMajorization, Some(distances)) => {
+            assert_eq!(
+                distances.num_points, num_points,
+                "distances were computed for a different graph"
+            );
+            &distances.distances
+        }
+        (LayoutAlgorithm::StressMajorization, None) => {
+            computed = GraphDistances::compute(num_points, &springs, &weights, &rest_lengths);
+            &computed.distances
+        }
+        _ => &[],
+    };
+
+    // Gradually reduce the number of dimensions while solving the constraints
+    let mut last_report = Instant::now();
+    let mut iterations = 0;
+    // A warm restart is already relaxed, and shaking it as hot as a fresh
+    // start would lose the arrangement it is meant to keep
+    let mut temperature = if start.is_some() {
+        params.min_temperature
+    } else {
+        params.initial_temperature
+    };
+    let final_dims = params.active_dims();
+    let levels = if params.flat {
+        2..3
+    } else if start.is_some() {
+        final_dims..final_dims + 1
+    } else {
+        final_dims..stride.max(final_dims + 1)
+    };
+    for dims in levels.rev() {
+        let mut converged = false;
+        for _ in 0..params.outer_iterations {
+            for _ in 0..params.inner_iterations {
+                let previous = points.clone();
+                // Pull nodes towards the origin to keep components together
+                if params.gravity != 0.0 {
+                    for (p, _) in points
+                        .chunks_mut(stride)
+                        .zip(&pinned)
+                        .filter(|(_, &pinned)| !pinned)
+                    {
+                        for v in &mut p[..dims] {
+                            *v -= temperature * params.gravity * *v;
+                        }
+                    }
+                }
+
+                match params.algorithm {
+                    LayoutAlgorithm::Legacy => {
+                        // The hierarchy axis is ignored while it is not active
+                        if let Some((rank_axis, rank_sign)) = rank.filter(|&(axis, _)| axis < dims)
+                        {
+                            // Move parents and children apart along the rank direction
+                            for (&(i, j), &factor) in springs.iter().zip(&float_factors) {
+                                let dz = rank_sign
+                                    * (points[i * stride + rank_axis]
+                                        - points[j * stride + rank_axis]);
+                                if dz < params.float_distance {
+                                    let shortfall =
+                                        (params.float_distance - dz) / params.float_distance;
+                                    let step = rank_sign
+                                        * temperature
+                                        * params.float_strength
+                                        * factor
+                                        * shortfall;
+                                    if !pinned[i] {
+                                        points[i * stride + rank_axis] += step;
+                                    }
+                                    if !pinned[j] {
+                                        points[j * stride + rank_axis] -= step;
+                                    }
+                                }
+                            }
+
+                            // Pull nodes of the same rank to a common level
+                            for group in &same_rank {
+                                let mean = group
+                                    .iter()
+                                    .map(|&i| points[i * stride + rank_axis])
+                                    .sum::<f32>()
+                                    / group.len() as f32;
+                                for &i in group.iter().filter(|&&i| !pinned[i]) {
+                                    let v = &mut points[i * stride + rank_axis];
+                                    *v += temperature * params.same_rank_strength * (mean - *v);
+                                }
+                            }
+                        }
+
+                        // Move nodes with many edges towards y+, except in
+                        // the plane where y is taken by the hierarchy
+                        for ((p, &incoming), _) in points
+                            .chunks_mut(stride)
+                            .zip(&num_incoming)
+                            .zip(&pinned)
+                            .filter(|(_, &pinned)| !pinned && !params.flat)
+                        {
+                            p[1] +=
+                                temperature * params.node_degree_strength * (incoming as f32 - p[1])
+                        }
+
+                        // Move nodes away from each other
+                        let displacements =
+                            forces::repulsion_displacements(&points, stride, dims, &masses, params);
+                        apply_displacements(
+                            &mut points,
+                            &pinned,
+                            stride,
+                            dims,
+                            &displacements,
+                            temperature,
+                            params.max_displacement,
+                        );
+
+                        // Move nodes to satisfy edge length
+                        let displacements = forces::edge_displacements(
+                            &points,
+                            stride,
+                            dims,
+                            &springs,
+                            &weights,
+                            &rest_lengths,
+                            params,
+                        );
+                        apply_displacements(
+                            &mut points,
+                            &pinned,
+                            stride,
+                            dims,
+                            &displacements,
+                            temperature,
+                            params.max_displacement,
+                        );
+                    }
+                    LayoutAlgorithm::FruchtermanReingold => {
+                        let k = (params.fr_area / num_points as f32).sqrt();
+                        let displacements = forces::fruchterman_reingold_displacements(
+                            &points, stride, dims, &springs, &weights, k,
+                        );
+                        // The temperature limits how far a node moves in each step
+                        apply_displacements(
+                            &mut points,
+                            &pinned,
+                            stride,
+                            dims,
+                            &displacements,
+                            1.0,
+                            (temperature * k).min(params.max_displacement),
+                        );
+                    }
+                    LayoutAlgorithm::StressMajorization => {
+                        let displacements =
+                            forces::stress_displacements(&points, stride, dims, distances);
+                        apply_displacements(
+                            &mut points,
+                            &pinned,
+                            stride,
+                            dims,
+                            &displacements,
+                            1.0,
+                            params.max_displacement,
+                        );
+                    }
+                }
+
+                limit_steps(&mut points, &previous, stride, dims, params.max_step);
+
+                iterations += 1;
+                temperature = (temperature * params.cooling_rate).max(params.min_temperature);
+                let moved: f32 = points
+                    .iter()
+                    .zip(&previous)
+                    .map(|(a, b)| (a - b) * (a - b))
+                    .sum();
+                if params.verbose && last_report.elapsed() >= REPORT_INTERVAL {
+                    last_report = Instant::now();
+                    eprintln!(
+                        "{} dims, iteration {}, total squared displacement {:.3e}",
+                        dims, iterations, moved
+                    );
+                }
+                if moved < params.convergence_threshold {
+                    converged = true;
+                    break;
+                }
+            }
+            on_step(&points, dims, iterations);
+            // Settled at this level, continue with the next lower one
+            if converged {
+                break;
+            }
+        }
+    }
+
+    // Bring a solve that stopped above 3D down to the three coordinates shown
+    if final_dims > 3 && params.pca_projection {
+        let projected = algorithms::principal_components(&points, stride, final_dims, 3);
+        for (p, q) in points.chunks_mut(stride).zip(projected.chunks(3)) {
+            p[..3].copy_from_slice(q);
+            p[3..].fill(0.0);
+        }
+    }
+    if let Some(size) = params.normalize_size {
+        normalize_points(&mut points, stride, size);
+    }
+    if final_dims > 3 || params.normalize_size.is_some() {
+        on_step(&points, 3, iterations);
+    }
+
+    Layout {
+        points,
+        iterations,
+        num_components,
+        components,
+    }
+}
+
+/// Copies the parsed `pos` attributes of the nodes that have one into
+/// `points`. Normally the values are used as they are, in 2 or 3 dimensions.
+/// With [`LayoutParams::warm_start_2d`] only x and y are used, centered and
+/// scaled so the edges between positioned nodes have `edge_length` on
+/// average, which suits the point units Graphviz writes. Every other
+/// coordinate keeps its random initial value.
+fn seed_positions(
+    points: &mut [f32],
+    stride: usize,
+    positions: &[Option<Vec<f32>>],
+    springs: &[(usize, usize)],
+    params: &LayoutParams,
+) {
+    if !params.warm_start_2d {
+        for (p, pos) in points.chunks_mut(stride).zip(positions) {
+            if let Some(pos) = pos {
+                p[..pos.len()].copy_from_slice(pos);
+            }
+        }
+        return;
+    }
+
+    let positioned = positions.iter().flatten().collect::<Vec<_>>();
+    if positioned.is_empty() {
+        return;
+    }
+    let mut center = [0.0; 2];
+    for pos in &positioned {
+        center[0] += pos[0] / positioned.len() as f32;
+        center[1] += pos[1] / positioned.len() as f32;
+    }
+    let lengths = springs
+        .iter()
+        .filter_map(|&(i, j)| {
+            let (a, b) = (positions[i].as_ref()?, positions[j].as_ref()?);
+            Some(((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt())
+        })
+        .filter(|&length| length > 0.0)
+        .collect::<Vec<_>>();
+    let scale = if lengths.is_empty() {
+        1.0
+    } else {
+        params.edge_length * lengths.len() as f32 / lengths.iter().sum::<f32>()
+    };
+    for (p, pos) in points.chunks_mut(stride).zip(positions) {
+        if let Some(pos) = pos {
+            p[0] = (pos[0] - center[0]) * scale;
+            p[1] = (pos[1] - center[1]) * scale;
+        }
+    }
+}
+
+/// Returns a random generator seeded with `seed`, or from entropy if `None`.
+pub fn make_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Returns the starting points for the solver, given the number of incoming
+/// edges of each node.
+pub fn initial_points<R: Rng>(
+    num_incoming: &[usize],
+    params: &LayoutParams,
+    rng: &mut R,
+) -> Vec<f32> {
+    let stride = params.max_dims;
+
+    let mut radius = params.init_radius;
+    if params.scale_init_radius {
+        radius *= (num_incoming.len() as f32).sqrt();
+    }
+
+    // Init points with random values in many dimensions
+    let mut points = vec![0.0; num_incoming.len() * stride];
+    for (p, &incoming) in points.chunks_mut(stride).zip(num_incoming) {
+        for v in p.iter_mut() {
+            *v = radius * rng.gen_range(-1.0..1.0);
+        }
+        p[1] = incoming as f32;
+    }
+    points
+}
+
+/// Moves each point that is not `pinned` by its displacement times `scale`,
+/// limited to `max_displacement`.
+fn apply_displacements(
+    points: &mut [f32],
+    pinned: &[bool],
+    stride: usize,
+    dims: usize,
+    displacements: &[f32],
+    scale: f32,
+    max_displacement: f32,
+) {
+    for ((p, displacement), _) in points
+        .chunks_mut(stride)
+        .zip(displacements.chunks(dims))
+        .zip(pinned)
+        .filter(|(_, &pinned)| !pinned)
+    {
+        let length = displacement.iter().map(|d| d * d).sum::<f32>().sqrt() * scale;
+        let scale = if length > max_displacement {
+            scale * max_displacement / length
+        } else {
+            scale
+        };
+        for (v, d) in p.iter_mut().zip(displacement) {
+            *v += scale * d;
+        }
+    }
+}
+
+/// Centers the first three coordinates of `points` on their centroid and
+/// scales them so that the largest side of their bounding box is `size`.
+fn normalize_points(points: &mut [f32], stride: usize, size: f32) {
+    let num_points = points.len() / stride;
+    if num_points == 0 {
+        return;
+    }
+    let mut centroid = [0.0; 3];
+    for p in points.chunks(stride) {
+        for k in 0..3 {
+            centroid[k] += p[k] / num_points as f32;
+        }
+    }
+    let bounds = metrics::bounding_box(points, stride);
+    let extent = bounds.size().into_iter().fold(0.0, f32::max);
+    // A single point or coincident points only get centered
+    let scale = if extent > 0.0 { size / extent } else { 1.0 };
+    for p in points.chunks_mut(stride) {
+        for k in 0..3 {
+            p[k] = (p[k] - centroid[k]) * scale;
+        }
+    }
+}
+
+/// Scales back the movement of each point since `previous` to at most
+/// `max_step`, so that the forces of one iteration together cannot fling a
+/// node across the layout.
+fn limit_steps(points: &mut [f32], previous: &[f32], stride: usize, dims: usize, max_step: f32) {
+    for (p, q) in points.chunks_mut(stride).zip(previous.chunks(stride)) {
+        let length = p[..dims]
+            .iter()
+            .zip(&q[..dims])
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum::<f32>()
+            .sqrt();
+        if length > max_step {
+            let scale = max_step / length;
+            for (a, b) in p[..dims].iter_mut().zip(&q[..dims]) {
+                *a = b + scale * (*a - b);
+            }
+        }
+    }
+}
+
+/// Euclidean distance between points `i` and `j` over the first `dims`
+/// coordinates, where `points` holds `stride` coordinates per point. A `dims`
+/// larger than `stride` is a bug in the caller. It panics in debug builds and
+/// is clamped to `stride` otherwise.
+pub fn points_distance(points: &[f32], stride: usize, i: usize, j: usize, dims: usize) -> f32 {
+    debug_assert!(dims <= stride, "{} dimensions of {}", dims, stride);
+    let dims = dims.min(stride);
+    let p1 = &points[i * stride..];
+    let p2 = &points[j * stride..];
+    let mut length_squared: f32 = 0.0;
+    for k in 0..dims {
+        let dk = p2[k] - p1[k];
+        length_squared += dk * dk;
+    }
+    length_squared.sqrt()
+}
+
+#[cfg(test)]
+mod test {
+
+    use graphviz_rust::dot_generator::*;
+    use graphviz_rust::dot_structures::*;
+    use graphviz_rust::parse;
+
+    use super::*;
+
+    fn average_edge_length(graph: &Graph, params: &LayoutParams) -> f32 {
+        let points = layout(graph, params).points;
+        let Flattened { edges, .. } = flatten_graph(graph);
+        let total: f32 = edges
+            .iter()
+            .map(|&(i, j)| points_distance(&points, params.max_dims, i, j, 3))
+            .sum();
+        total / edges.len() as f32
+    }
+
+    #[test]
+    fn seeded_initial_points_test() {
+        let params = LayoutParams {
+            seed: Some(42),
+            ..Default::default()
+        };
+        let num_incoming = [0, 1, 2, 1];
+        let a = initial_points(&num_incoming, &params, &mut make_rng(params.seed));
+        let b = initial_points(&num_incoming, &params, &mut make_rng(params.seed));
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    #[ignore = "solves a 2000-node graph twice"]
+    fn init_radius_convergence_test() {
+        let tree = generators::random_tree(2000, 0);
+        let iterations = |scale_init_radius| {
+            let params = LayoutParams {
+                seed: Some(0),
+                scale_init_radius,
+                ..Default::default()
+            };
+            layout(&tree, &params).iterations
+        };
+        let (fixed, scaled) = (iterations(false), iterations(true));
+        assert!(scaled < fixed, "{} < {}", scaled, fixed);
+    }
+
+    #[test]
+    fn init_radius_test() {
+        let num_incoming = [0; 16];
+        let within = |params: &LayoutParams, radius: f32| {
+            let points = initial_points(&num_incoming, params, &mut make_rng(Some(3)));
+            let stride = params.max_dims;
+            points.chunks(stride).all(|p| {
+                p.iter()
+                    .enumerate()
+                    .all(|(k, v)| k == 1 || v.abs() <= radius)
+            })
+        };
+        let params = LayoutParams {
+            init_radius: 0.5,
+            ..Default::default()
+        };
+        assert!(within(&params, 0.5));
+        let params = LayoutParams {
+            scale_init_radius: true,
+            ..params
+        };
+        assert!(within(&params, 2.0));
+        assert!(!within(&params, 0.5));
+    }
+
+    #[test]
+    fn coincident_nodes_test() {
+        let g = parse(r#"digraph { a [pos="0,0,0"]; b [pos="0,0,0"]; a -> b }"#).unwrap();
+        let params = LayoutParams {
+            seed: Some(1),
+            max_dims: 3,
+            ..Default::default()
+        };
+        let points = layout(&g, &params).points;
+        assert!(points.iter().all(|v| v.is_finite()), "{:?}", points);
+        assert!(points_distance(&points, 3, 0, 1, 3) > 0.1, "{:?}", points);
+    }
+
+    #[test]
+    fn warm_start_2d_test() {
+        let params = LayoutParams {
+            warm_start_2d: true,
+            edge_length: 2.0,
+            max_dims: 4,
+            ..Default::default()
+        };
+        let mut points = vec![5.0; 3 * 4];
+        let positions = vec![
+            Some(vec![100.0, 100.0]),
+            Some(vec![172.0, 100.0, 9.0]),
+            None,
+        ];
+        seed_positions(&mut points, 4, &positions, &[(0, 1), (1, 2)], &params);
+        assert_eq!(
+            points,
+            vec![-1.0, 0.0, 5.0, 5.0, 1.0, 0.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0]
+        );
+    }
+
+    #[test]
+    fn hierarchy_axis_test() {
+        let g = parse("digraph { a -> b }").unwrap();
+        for axis in [0, 2] {
+            let params = LayoutParams {
+                seed: Some(3),
+                hierarchy_axis: Some(axis),
+                ..Default::default()
+            };
+            let points = layout(&g, &params).points;
+            let stride = params.max_dims;
+            assert!(points[axis] > points[stride + axis], "{:?}", points);
+        }
+    }
+
+    #[test]
+    fn degree_repulsion_test() {
+        let g = parse(
+            "graph { hub -- a; hub -- b; hub -- c; hub -- d; hub -- e; hub -- f; hub -- g; hub -- h }",
+        )
+        .unwrap();
+        let params = LayoutParams {
+            seed: Some(5),
+            ..Default::default()
+        };
+        let spread = LayoutParams {
+            degree_repulsion_exponent: 1.0,
+            ..params.clone()
+        };
+        let plain = average_edge_length(&g, &params);
+        let scaled = average_edge_length(&g, &spread);
+        assert!(scaled > plain, "{} <= {}", scaled, plain);
+    }
+
+    #[test]
+    fn pinned_nodes_test() {
+        let g = parse(
+            r#"digraph {
+                a [pos="1,2,3" pin=true]
+                b [pos="4,5!"]
+                c [pos="7,8,9"]
+                a -> b -> c -> d
+            }"#,
+        )
+        .unwrap();
+        let params = LayoutParams {
+            seed: Some(5),
+            gravity: 0.1,
+            ..Default::default()
+        };
+        let points = layout(&g, &params).points;
+        let stride = params.max_dims;
+        assert_eq!(points[..3], [1.0, 2.0, 3.0]);
+        assert_eq!(points[stride..stride + 3], [4.0, 5.0, 0.0]);
+        assert_ne!(points[2 * stride..2 * stride + 3], [7.0, 8.0, 9.0]);
+    }
+
+    #[test]
+    fn graph_distances_test() {
+        let g = parse("graph { a -- b -- c; b -- d [weight=2]; e }").unwrap();
+        let params = LayoutParams {
+            algorithm: LayoutAlgorithm::StressMajorization,
+            seed: Some(6),
+            ..Default::default()
+        };
+        let distances = GraphDistances::new(&g, &params);
+        assert_eq!(distances.distance(0, 2), 2.0);
+        assert_eq!(distances.distance(0, 3), 1.5);
+        assert_eq!(distances.distance(0, 4), f32::INFINITY);
+        assert_eq!(
+            layout_with_distances(&g, &params, &distances),
+            layout(&g, &params)
+        );
+    }
+
+    #[test]
+    fn flat_layout_test() {
+        let g = parse("digraph { a -> b -> c; x -> y }").unwrap();
+        let params = LayoutParams {
+            seed: Some(7),
+            flat: true,
+            ..Default::default()
+        };
+        let mut levels = Vec::new();
+        let points = layout_with_progress(&g, &params, |_, dims| levels.push(dims)).points;
+        assert!(levels.iter().all(|&dims| dims == 2), "{:?}", levels);
+        for p in points.chunks(params.max_dims) {
+            assert!(p[2..].iter().all(|&v| v == 0.0), "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn normalize_points_test() {
+        let mut points = vec![1.0, 2.0, 3.0, 9.0, 5.0, 2.0, 3.0, 9.0, 3.0, 8.0, 3.0, 9.0];
+        normalize_points(&mut points, 4, 2.0);
+        let third = 1.0 / 3.0;
+        #[rustfmt::skip]
+        let expected = [
+            -2.0 * third, -2.0 * third, 0.0, 9.0,
+            2.0 * third, -2.0 * third, 0.0, 9.0,
+            0.0, 4.0 * third, 0.0, 9.0,
+        ];
+        for (a, b) in points.iter().zip(&expected) {
+            assert!((a - b).abs() < 1e-5, "{:?}", points);
+        }
+    }
+
+    #[test]
+    fn limit_steps_test() {
+        let previous = vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 5.0, 5.0, 5.0];
+        let mut points = vec![3.0, 4.0, 9.0, 1.1, 1.0, 1.0, -5.0, 5.0, 5.0];
+        limit_steps(&mut points, &previous, 3, 2, 0.5);
+        for (p, q) in points.chunks(3).zip(previous.chunks(3)) {
+            let step = ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2)).sqrt();
+            assert!(step <= 0.5 + 1e-6, "{:?} from {:?}", p, q);
+        }
+        assert!((points[0] - 0.3).abs() < 1e-6 && (points[1] - 0.4).abs() < 1e-6);
+        assert_eq!(points[2], 9.0);
+        assert_eq!(points[3..6], [1.1, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn pca_projection_test() {
+        let g = parse("digraph { a -> b -> c -> d; a -> c }").unwrap();
+        let params = LayoutParams {
+            seed: Some(4),
+            final_dims: 5,
+            pca_projection: true,
+            ..Default::default()
+        };
+        let mut last_dims = 0;
+        let points = layout_streaming(&g, &params, |_, dims, _| last_dims = dims).points;
+        assert_eq!(last_dims, 3);
+        for p in points.chunks(params.max_dims) {
+            assert!(p[3..].iter().all(|&v| v == 0.0), "{:?}", p);
+        }
+        let mean_x = points.chunks(params.max_dims).map(|p| p[0]).sum::<f32>() / 4.0;
+        assert!(mean_x.abs() < 1e-4);
+    }
+
+    #[test]
+    fn len_attribute_test() {
+        let g = parse("graph { a -- b [len=3]; c -- d; e -- f [len=oops] }").unwrap();
+        let params = LayoutParams {
+            seed: Some(8),
+            ..Default::default()
+        };
+        let Flattened {
+            edges,
+            edge_attributes: attributes,
+            ..
+        } = flatten_graph(&g);
+        let (_, _, rest_lengths) = springs(&edges, &attributes, &params);
+        assert_eq!(rest_lengths, vec![3.0, 1.0, 1.0]);
+        let points = layout(&g, &params).points;
+        let stride = params.max_dims;
+        let long = points_distance(&points, stride, 0, 1, 3);
+        let short = points_distance(&points, stride, 2, 3, 3);
+        assert!(long > short, "{} <= {}", long, short);
+    }
+
+    #[test]
+    fn flat_graph_test() {
+        let g = parse(
+            r#"digraph {
+                node [color=red]
+                a [label="first"]
+                subgraph s { b:p -> c [label="to c"] }
+                a -> b
+            }"#,
+        )
+        .unwrap();
+        let flat = FlatGraph::new(&g);
+        let ids = flat.nodes.iter().map(|n| n.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        assert_eq!(flat.nodes[0].label.as_deref(), Some("first"));
+        assert_eq!(flat.nodes[1].label, None);
+        assert_eq!(
+            attribute_value(&flat.nodes[2].attributes, "color"),
+            Some("red")
+        );
+        assert_eq!(flat.edges_indices(), vec![(1, 2), (0, 1)]);
+        assert_eq!(flat.edges[0].label.as_deref(), Some("to c"));
+        assert_eq!(flat.edges[1].label, None);
+
+        let g = parse(r#"digraph G { a [label="\N of \G"]; a -> b [label="\N\G"] }"#).unwrap();
+        let flat = FlatGraph::new(&g);
+        assert_eq!(flat.nodes[0].label.as_deref(), Some("a of G"));
+        assert_eq!(flat.edges[0].label.as_deref(), Some("G"));
+    }
+
+    #[test]
+    fn graph_title_test() {
+        let g =
+            parse(r#"digraph "My graph" { label="Top"; a -> b; subgraph s { label="Inner" } }"#)
+                .unwrap();
+        assert_eq!(graph_id(&g).as_deref(), Some("My graph"));
+        assert_eq!(graph_label(&g).as_deref(), Some("Top"));
+        let g = parse(r#"graph G { label="Graph \G" }"#).unwrap();
+        assert_eq!(graph_label(&g).as_deref(), Some("Graph G"));
+        let g = parse("graph { a -- b }").unwrap();
+        assert_eq!(graph_id(&g), None);
+        assert_eq!(graph_label(&g), None);
+    }
+
+    #[test]
+    fn points_distance_test() {
+        let points = [0.0, 0.0, 3.0, 4.0];
+        assert_eq!(points_distance(&points, 2, 0, 1, 2), 5.0);
+        assert_eq!(points_distance(&points, 2, 0, 1, 1), 3.0);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn points_distance_clamp_test() {
+        // Reading past the last point would panic
+        let points = [0.0, 0.0, 3.0, 4.0];
+        assert_eq!(points_distance(&points, 2, 0, 1, 10), 5.0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn points_distance_dims_test() {
+        points_distance(&[0.0, 0.0, 3.0, 4.0], 2, 0, 1, 10);
+    }
+
+    #[test]
+    fn edge_length_param_test() {
+        let g = parse("digraph { a -> b -> c -> a; c -> d; d -> e }").unwrap();
+        let params = LayoutParams {
+            seed: Some(5),
+            ..Default::default()
+        };
+        let short = average_edge_length(&g, &params);
+        let long = average_edge_length(
+            &g,
+            &LayoutParams {
+                edge_length: 4.0,
+                ..params.clone()
+            },
+        );
+        assert!(long > short, "{} should exceed {}", long, short);
+    }
+
+    #[test]
+    fn clusters_test() {
+        let g = parse(
+            r#"digraph {
+                subgraph cluster_outer {
+                    label="Outer"
+                    a
+                    subgraph cluster_inner { b -> c }
+                }
+                subgraph plain { d }
+            }"#,
+        )
+        .unwrap();
+        let Flattened { nodes, .. } = flatten_graph(&g);
+        let clusters = clusters(&g, &nodes);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].name, "cluster_outer");
+        assert_eq!(
+            attribute_value(&clusters[0].attributes, "label"),
+            Some("Outer")
+        );
+        assert_eq!(clusters[0].nodes, vec![0, 1, 2]);
+        assert_eq!(clusters[0].parent, None);
+        assert_eq!(clusters[1].nodes, vec![1, 2]);
+        assert_eq!(clusters[1].parent, Some(0));
+    }
+
+    #[test]
+    fn decode_dot_test() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let dot = "digraph { a -> b }";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(dot.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        assert_eq!(decode_dot(gzipped).unwrap(), dot);
+        assert_eq!(decode_dot(dot.as_bytes().to_vec()).unwrap(), dot);
+        assert!(matches!(
+            decode_dot(vec![0xff, 0xfe]),
+            Err(GraphViz3dError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn keep_highest_degree_test() {
+        let g = parse("digraph { x [color=red]; a -> hub -> b; hub -> c; d -> e }").unwrap();
+        let Flattened { nodes, edges, .. } = flatten_graph(&keep_highest_degree(&g, 3));
+        let names = nodes
+            .iter()
+            .map(|n| id_value(&n.id.0).to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "hub", "b"]);
+        assert_eq!(edges, vec![(0, 1), (1, 2)]);
+
+        // A chain through a dropped node is split around it
+        let g = parse("graph { a -- b -- c -- d; a -- c; b -- d; a -- d }").unwrap();
+        let Flattened { nodes, edges, .. } = flatten_graph(&keep_highest_degree(&g, 3));
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(edges.len(), 3);
+    }
+
+    #[test]
+    fn filter_nodes_test() {
+        let g = parse(
+            r#"digraph {
+                node [group=core]
+                a; b [tier=1]; c [tier=1]
+                d [group=extra tier=1]
+                a -> b -> c -> d; b -> d
+            }"#,
+        )
+        .unwrap();
+        let filters = [("group".to_string(), "core".to_string())];
+        let Flattened { nodes, edges, .. } = flatten_graph(&filter_nodes(&g, &filters));
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(edges, vec![(0, 1), (1, 2)]);
+
+        let filters = [
+            ("group".to_string(), "core".to_string()),
+            ("tier".to_string(), "1".to_string()),
+        ];
+        let Flattened { nodes, edges, .. } = flatten_graph(&filter_nodes(&g, &filters));
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(edges, vec![(0, 1)]);
+    }
+
+    #[test]
+    fn split_graphs_test() {
+        let dot = r#"
+            // first } graph
+            digraph a { x -> y [label="}{"] z [label=<<b>{</b>>] }
+            /* { */
+            graph b {
+                subgraph { u -- v }
+            }
+        "#;
+        let graphs = split_graphs(dot);
+        assert_eq!(graphs.len(), 2);
+        assert!(graphs[0].trim_start().starts_with("// first"));
+        assert!(graphs[1].trim().starts_with("/* { */"));
+        let graphs = parse_graphs("digraph { x -> y } graph { u -- v }").unwrap();
+        assert!(is_directed(&graphs[0]));
+        assert!(!is_directed(&graphs[1]));
+        assert_eq!(parse_graphs("digraph { a }").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parse_graph_error_test() {
+        assert!(matches!(
+            parse_graph("digraph {"),
+            Err(GraphViz3dError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn preprocessor_lines_test() {
+        let g = parse_graph("# 1 \"gg.dot\"\ndigraph {\n  #line 3\n  a -> b\n}").unwrap();
+        assert_eq!(flatten_graph(&g).edges, vec![(0, 1)]);
+
+        let error = parse_graphs("graph { a }\ngraph {\n  a --\n}").unwrap_err();
+        assert!(error.to_string().contains("line 4, column 1"), "{}", error);
+    }
+
+    #[test]
+    fn chain_with_subgraph_test() {
+        let mut g = parse("digraph { aa -> be -> subgraph v { d -> aaa } -> ee }").unwrap();
+        // The parser rejects edges starting with a subgraph, so add
+        // `subgraph w { x; y } -> z` by hand.
+        let Graph::DiGraph { stmts, .. } = &mut g else {
+            unreachable!()
+        };
+        stmts.push(stmt!(edge!(
+            subgraph!("w"; node!("x"), node!("y")) => node_id!("z")
+        )));
+        let Flattened { nodes, edges, .. } = flatten_graph(&g);
+        let name = |i: usize| id_value(&nodes[i].id.0).to_string();
+        let mut edges = edges
+            .iter()
+            .map(|&(i, j)| format!("{}->{}", name(i), name(j)))
+            .collect::<Vec<_>>();
+        edges.sort();
+        assert_eq!(
+            edges,
+            vec!["aa->be", "aaa->ee", "be->aaa", "be->d", "d->aaa", "d->ee", "x->z", "y->z"]
+        );
+    }
+
+    #[test]
+    fn anonymous_subgraphs_test() {
+        let g = parse(
+            r#"digraph {
+                subgraph { a [color=red] }
+                subgraph { b [color=blue] }
+                subgraph cluster_x { subgraph { c } subgraph { d } }
+            }"#,
+        )
+        .unwrap();
+        let Flattened { nodes, .. } = flatten_graph(&g);
+        let names = nodes.iter().map(|n| id_value(&n.id.0)).collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b", "c", "d"]);
+        assert_eq!(attribute_value(&nodes[0].attributes, "color"), Some("red"));
+        assert_eq!(attribute_value(&nodes[1].attributes, "color"), Some("blue"));
+        let clusters = clusters(&g, &nodes);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].nodes, vec![2, 3]);
+    }
+
+    #[test]
+    fn anonymous_subgraph_id_collision_test() {
+        // Sibling anonymous subgraphs sharing the same made-up id, which even
+        // looks like a cluster name, still keep their nodes apart.
+        let anonymous = |stmts: Vec<Stmt>| Subgraph {
+            id: Id::Anonymous("cluster_0".to_string()),
+            stmts,
+        };
+        let g = graph!(di id!();
+            anonymous(vec![stmt!(node!("a"; attr!("color", "red")))]),
+            anonymous(vec![stmt!(node!("b"; attr!("color", "blue")))])
+        );
+        let Flattened { nodes, .. } = flatten_graph(&g);
+        let names = nodes.iter().map(|n| id_value(&n.id.0)).collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(attribute_value(&nodes[0].attributes, "color"), Some("red"));
+        assert_eq!(attribute_value(&nodes[1].attributes, "color"), Some("blue"));
+        assert!(clusters(&g, &nodes).is_empty());
+    }
+
+    #[test]
+    fn pair_with_subgraphs_test() {
+        let mut g = parse("graph { e -- subgraph { f } }").unwrap();
+        // The parser rejects edges starting with a subgraph, so add
+        // `subgraph { a b } -- subgraph s { c -- d }` by hand.
+        let Graph::Graph { stmts, .. } = &mut g else {
+            unreachable!()
+        };
+        stmts.push(stmt!(edge!(
+            subgraph!(; node!("a"), node!("b")) =>
+            subgraph!("s"; edge!(node_id!("c") => node_id!("d")))
+        )));
+        let Flattened { nodes, edges, .. } = flatten_graph(&g);
+        let name = |i: usize| id_value(&nodes[i].id.0).to_string();
+        let mut edges = edges
+            .iter()
+            .map(|&(i, j)| format!("{}-{}", name(i), name(j)))
+            .collect::<Vec<_>>();
+        edges.sort();
+        assert_eq!(edges, vec!["a-c", "a-d", "b-c", "b-d", "c-d", "e-f"]);
+    }
+
+    #[test]
+    fn id_to_string_test() {
+        let plain = Id::Plain("a".to_string());
+        assert_eq!(id_to_string(&plain), "plain a");
+        assert_eq!(id_to_string(&Id::Escaped("\"a\"".to_string())), "esc \"a\"");
+        assert_eq!(
+            id_to_string(&Id::Html("<b>a</b>".to_string())),
+            "html <b>a</b>"
+        );
+        assert_eq!(id_to_string(&Id::Anonymous("1".to_string())), "anon 1");
+    }
+
+    #[test]
+    fn port_to_string_test() {
+        let p = Id::Plain("p".to_string());
+        assert_eq!(port_to_string(&Port(None, None)), "");
+        assert_eq!(port_to_string(&Port(Some(p.clone()), None)), "plain p");
+        assert_eq!(port_to_string(&Port(None, Some("n".to_string()))), ":n");
+        assert_eq!(
+            port_to_string(&Port(Some(p), Some("sw".to_string()))),
+            "plain p:sw"
+        );
+    }
+
+    #[test]
+    fn node_id_to_string_test() {
+        let a = Id::Plain("a".to_string());
+        let p = Id::Plain("p".to_string());
+        assert_eq!(node_id_to_string(&NodeId(a.clone(), None)), "plain a");
+        assert_eq!(
+            node_id_to_string(&NodeId(a.clone(), Some(Port(Some(p.clone()), None)))),
+            "plain a:plain p"
+        );
+        assert_eq!(
+            node_id_to_string(&NodeId(
+                a.clone(),
+                Some(Port(Some(p), Some("n".to_string())))
+            )),
+            "plain a:plain p:n"
+        );
+        assert_eq!(
+            node_id_to_string(&NodeId(a, Some(Port(None, Some("n".to_string()))))),
+            "plain a::n"
+        );
+    }
+
+    #[test]
+    fn ports_test() {
+        let g = parse("digraph { a:p1 -> b; a:p2:n -> b; b -> a }").unwrap();
+        let Flattened {
+            nodes,
+            edges,
+            edge_attributes,
+        } = flatten_graph(&g);
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].id, NodeId(Id::Plain("a".to_string()), None));
+        assert_eq!(edges, vec![(0, 1), (0, 1), (1, 0)]);
+        assert_eq!(attribute_value(&edge_attributes[0], "tailport"), Some("p1"));
+        assert_eq!(
+            attribute_value(&edge_attributes[1], "tailport"),
+            Some("p2:n")
+        );
+        assert_eq!(attribute_value(&edge_attributes[2], "headport"), None);
+    }
+
+    #[test]
+    fn empty_graph_test() {
+        let g = parse("digraph {}").unwrap();
+        let layout = layout(&g, &LayoutParams::default());
+        assert!(layout.points.is_empty());
+        assert_eq!(layout.num_components, 0);
+    }
+
+    #[test]
+    fn single_node_test() {
+        let g = parse("digraph { a }").unwrap();
+        let mut calls = 0;
+        let layout = layout_with_progress(&g, &LayoutParams::default(), |_, _| calls += 1);
+        assert_eq!(calls, 1);
+        assert_eq!(layout.points, vec![0.0; MAX_DIMS]);
+        assert_eq!(layout.components, vec![0]);
+    }
+
+    #[test]
+    fn default_attributes_test() {
+        let g = parse(
+            r#"digraph {
+                node [shape=box]
+                a
+                node [shape=circle color=red]
+                b [shape=square]
+                a -> c [weight=2]
+                subgraph s { node [color=blue] d }
+                e
+                edge [weight=3]
+                e -> a
+            }"#,
+        )
+        .unwrap();
+        let Flattened {
+            nodes,
+            edge_attributes,
+            ..
+        } = flatten_graph(&g);
+        let value = |i: usize, key| attribute_value(&nodes[i].attributes, key);
+        let names = nodes
+            .iter()
+            .map(|node| id_value(&node.id.0))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(value(0, "shape"), Some("box"));
+        assert_eq!(value(1, "shape"), Some("square"));
+        assert_eq!(value(1, "color"), Some("red"));
+        assert_eq!(value(2, "shape"), Some("circle"));
+        assert_eq!(value(3, "color"), Some("blue"));
+        assert_eq!(value(4, "color"), Some("red"));
+        assert_eq!(attribute_value(&edge_attributes[0], "weight"), Some("2"));
+        assert_eq!(attribute_value(&edge_attributes[1], "weight"), Some("3"));
+    }
+
+    #[test]
+    fn relayout_streaming_test() {
+        // Enough iterations for the first layout to settle
+        let params = LayoutParams {
+            seed: Some(2),
+            outer_iterations: 100,
+            ..Default::default()
+        };
+        let stride = params.max_dims;
+        let g = parse("graph { a -- b -- c -- a }").unwrap();
+        let before = layout(&g, &params).points;
+        let Flattened { nodes, .. } = flatten_graph(&g);
+        let start = nodes
+            .iter()
+            .zip(before.chunks(stride))
+            .map(|(node, p)| (node_key(&node.id), p.to_vec()))
+            .collect::<HashMap<_, _>>();
+
+        // The triangle is already relaxed and the restart is cool, so no node
+        // moves more than a hundredth of an edge length
+        let after = relayout_streaming(&g, &params, &start, |_, dims, _| {
+            assert_eq!(dims, params.final_dims)
+        })
+        .points;
+        for (p, q) in before.chunks(stride).zip(after.chunks(stride)) {
+            let moved = (0..3).map(|k| (q[k] - p[k]).powi(2)).sum::<f32>().sqrt();
+            assert!(moved < 0.01 * params.edge_length, "moved {}", moved);
+        }
+
+        // A new node starts at random and settles next to its neighbor
+        let edited = parse("graph { a -- b -- c -- a; c -- d }").unwrap();
+        let after = relayout_streaming(&edited, &params, &start, |_, _, _| {}).points;
+        assert_eq!(after.len(), 4 * stride);
+        let length = points_distance(&after, stride, 2, 3, 3);
+        assert!(length < 2.0 * params.edge_length, "{}", length);
+    }
+
+    #[test]
+    fn layout_streaming_test() {
+        let g = parse("digraph { a -> b -> c }").unwrap();
+        let params = LayoutParams {
+            seed: Some(2),
+            ..Default::default()
+        };
+        let mut steps = Vec::new();
+        let layout = layout_streaming(&g, &params, |points, dims, iterations| {
+            assert_eq!(points.len(), 3 * params.max_dims);
+            steps.push((dims, iterations));
+        });
+        assert!(steps
+            .windows(2)
+            .all(|w| w[0].0 >= w[1].0 && w[0].1 < w[1].1));
+        assert_eq!(steps.last(), Some(&(3, layout.iterations)));
+    }
+
+    #[test]
+    fn strict_dedup_test() {
+        let g = parse("strict graph { a -- b [color=red]; b -- a [weight=2]; a -- a; a -- a }")
+            .unwrap();
+        let Flattened {
+            edges,
+            edge_attributes: attributes,
+            ..
+        } = flatten_graph(&g);
+        assert_eq!(edges, vec![(0, 1), (0, 0)]);
+        assert_eq!(attribute_value(&attributes[0], "color"), Some("red"));
+        assert_eq!(attribute_value(&attributes[0], "weight"), Some("2"));
+
+        let g =
+            parse("strict digraph { a -> b; b -> a; a -> b [color=red]; a -> a; a -> a }").unwrap();
+        let Flattened {
+            edges,
+            edge_attributes: attributes,
+            ..
+        } = flatten_graph(&g);
+        assert_eq!(edges, vec![(0, 1), (1, 0), (0, 0)]);
+        assert_eq!(attribute_value(&attributes[0], "color"), Some("red"));
+        assert_eq!(attribute_value(&attributes[1], "color"), None);
+
+        let g = parse("graph { a -- b; b -- a }").unwrap();
+        assert_eq!(flatten_graph(&g).edges, vec![(0, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn parse_test() {
+        let g: Graph = parse(
+            r#"
+        strict digraph t {
+            aa[color=green]
+            subgraph v {
+                aa[shape=square]
+                subgraph vv{a2 -> b2}
+                aaa[color=red]
+                aaa -> bbb
+            }
+            aa -> be -> subgraph v { d -> aaa}
+            aa -> aaa -> v
+        }
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            g,
+            graph!(strict di id!("t");
+            node!("aa";attr!("color","green")),
+            subgraph!("v";
+            node!("aa"; attr!("shape","square")),
+            subgraph!("vv"; edge!(node_id!("a2") => node_id!("b2"))),
+            node!("aaa";attr!("color","red")),
+            edge!(node_id!("aaa") => node_id!("bbb"))
+                ),
+                edge!(node_id!("aa") => node_id!("be") => subgraph!("v"; edge!(node_id!("d") => node_id!("aaa")))),
+                edge!(node_id!("aa") => node_id!("aaa") => node_id!("v"))
+            )
+        )
+    }
+}
@@ -1,339 +1,1699 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use clap::{error::ErrorKind, CommandFactory, Parser, ValueEnum};
+use graphviz3d::algorithms::{
+    back_edges, connected_components, degrees, minimum_spanning_tree, pagerank, shortest_path,
+};
+use graphviz3d::attributes::{attribute_value, id_value, parse_weight};
+use graphviz3d::bundling::{bundle_edges, BundleParams};
+use graphviz3d::color::{hash_color, parse_color_in, spread_color};
+use graphviz3d::export::{export_dot, export_obj, export_ply, FrameExport, LayoutExport};
+use graphviz3d::graphml::{is_graphml, parse_graphml};
+use graphviz3d::label::{label_text, LabelEscapes};
+use graphviz3d::metrics::{bounding_box, layout_metrics, BoundingBox};
+use graphviz3d::shapes::{
+    degree_radii, score_radii, shape_style, Glyph, LineStyle, ShapeStyle, DEFAULT_STYLE,
+};
+use graphviz3d::{
+    clusters, decode_dot, filter_nodes, flatten_graph, graph_id, graph_label, is_directed,
+    keep_highest_degree, layout_streaming, make_rng, node_key, parse_graphs, points_distance,
+    relayout_streaming, FlatGraph, Flattened, GraphDistances, GraphViz3dError, LayoutAlgorithm,
+    LayoutParams, STRESS_WARN_NODES,
+};
 use graphviz_rust::dot_structures::*;
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, Rng};
 use rerun::{
-    components::{Arrow3D, ColorRGBA, Label, Point3D, Radius},
-    MsgSender,
+    components::{Arrow3D, Box3D, ColorRGBA, Label, LineStrip3D, Point3D, Radius, Vec3D},
+    time::Timeline,
+    MsgSender, Session,
 };
+use serde::{Deserialize, Serialize};
 
-const MAX_DIMS: usize = 10;
+trait Lerpable {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
 
-pub fn id_to_string(id: &Id) -> String {
-    match id {
-        Id::Html(ref v) => format!("html {}", v),
-        Id::Escaped(ref v) => format!("esc {}", v),
-        Id::Plain(ref v) => format!("plain {}", v),
-        Id::Anonymous(ref v) => format!("anon {}", v),
+impl Lerpable for u8 {
+    fn lerp(self, other: u8, t: f32) -> u8 {
+        (self as f32 * (1.0 - t) + other as f32 * t).round() as u8
     }
 }
 
-pub fn port_to_string(port: &Port) -> String {
-    match port {
-        Port(None, None) => "".to_string(),
-        Port(Some(ref id), None) => id_to_string(id),
-        Port(None, Some(ref dir)) => format!(":{}", dir),
-        Port(Some(ref id), Some(ref dir)) => format!("{}:{}", id_to_string(id), dir),
+impl Lerpable for f32 {
+    fn lerp(self, other: f32, t: f32) -> f32 {
+        self * (1.0 - t) + other * t
     }
 }
 
-pub fn node_id_to_string(node_id: &NodeId) -> String {
-    match node_id.1 {
-        None => id_to_string(&node_id.0),
-        Some(ref port) => format!("{}:{}", id_to_string(&node_id.0), port_to_string(port)),
+impl Lerpable for ColorRGBA {
+    fn lerp(self, other: ColorRGBA, t: f32) -> ColorRGBA {
+        let [r0, g0, b0, a0] = self.to_array();
+        let [r1, g1, b1, a1] = other.to_array();
+        ColorRGBA::from_unmultiplied_rgba(
+            r0.lerp(r1, t),
+            g0.lerp(g1, t),
+            b0.lerp(b1, t),
+            a0.lerp(a1, t),
+        )
     }
 }
 
-trait Lerpable {
-    fn lerp(self, other: Self, t: f32) -> Self;
+/// Colors edges by how far their length is from the rest length.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct ColorScheme {
+    /// Length at which an edge gets `relaxed_color`. Always the edge length
+    /// of the layout, so it is not part of the config file.
+    #[serde(skip)]
+    reference_length: f32,
+    /// How much shorter than the reference an edge is when it reaches
+    /// `compressed_color`.
+    compression_range: f32,
+    /// How much longer than the reference an edge is when it reaches
+    /// `stretched_color`.
+    stretch_range: f32,
+    #[serde(with = "rgba")]
+    compressed_color: ColorRGBA,
+    #[serde(with = "rgba")]
+    relaxed_color: ColorRGBA,
+    #[serde(with = "rgba")]
+    stretched_color: ColorRGBA,
+    /// Color of highlighted edges, which ignore the length gradient.
+    #[serde(with = "rgba")]
+    highlight_color: ColorRGBA,
+    /// Opacity of the edges that are not highlighted, when highlighting.
+    faint_alpha: u8,
+    /// Color of edges that close a cycle, with `--back-edges`.
+    #[serde(with = "rgba")]
+    back_edge_color: ColorRGBA,
 }
 
-impl Lerpable for u8 {
-    fn lerp(self, other: u8, t: f32) -> u8 {
-        (self as f32 * (1.0 - t) + other as f32 * t).round() as u8
+impl Default for ColorScheme {
+    fn default() -> Self {
+        Self {
+            reference_length: 1.0,
+            compression_range: 0.5,
+            stretch_range: 5.0,
+            compressed_color: ColorRGBA::from_rgb(255, 0, 0),
+            relaxed_color: ColorRGBA::from_rgb(0, 255, 0),
+            stretched_color: ColorRGBA::from_rgb(127, 0, 255),
+            highlight_color: ColorRGBA::from_rgb(255, 200, 0),
+            faint_alpha: 40,
+            back_edge_color: ColorRGBA::from_rgb(0, 200, 255),
+        }
+    }
+}
+
+impl ColorScheme {
+    fn edge_color(&self, length: f32) -> ColorRGBA {
+        let target = if length < self.reference_length {
+            self.compressed_color
+        } else {
+            self.stretched_color
+        };
+        self.relaxed_color.lerp(target, self.strain(length))
+    }
+
+    /// Returns how far an edge of `length` is from the reference length, from
+    /// 0 when relaxed to 1 at the end of the compression or stretch range.
+    fn strain(&self, length: f32) -> f32 {
+        let t = if length < self.reference_length {
+            (self.reference_length - length) / self.compression_range
+        } else {
+            (length - self.reference_length) / self.stretch_range
+        };
+        t.clamp(0.0, 1.0)
+    }
+
+    fn faint(&self, color: ColorRGBA) -> ColorRGBA {
+        let [r, g, b, _] = color.to_array();
+        ColorRGBA::from_unmultiplied_rgba(r, g, b, self.faint_alpha)
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut rng = thread_rng();
+/// Serializes colors as `[r, g, b, a]` arrays in config files.
+mod rgba {
+    use rerun::components::ColorRGBA;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &ColorRGBA, serializer: S) -> Result<S::Ok, S::Error> {
+        color.to_array().serialize(serializer)
+    }
 
-    let dot = std::fs::read_to_string("../Reconstruction/docs/graphviz/cmake/gg.dot")?;
-    let g: Graph = graphviz_rust::parse(dot.as_str())?;
-    let statements = match g {
-        Graph::Graph {
-            id: _,
-            strict: _,
-            ref stmts,
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ColorRGBA, D::Error> {
+        let [r, g, b, a] = <[u8; 4]>::deserialize(deserializer)?;
+        Ok(ColorRGBA::from_unmultiplied_rgba(r, g, b, a))
+    }
+}
+
+/// Contents of a `--config` file.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Config {
+    layout: LayoutParams,
+    colors: ColorScheme,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Algorithm {
+    Legacy,
+    /// Fruchterman-Reingold
+    Fr,
+    /// Stress majorization
+    Stress,
+}
+
+impl From<Algorithm> for LayoutAlgorithm {
+    fn from(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Legacy => LayoutAlgorithm::Legacy,
+            Algorithm::Fr => LayoutAlgorithm::FruchtermanReingold,
+            Algorithm::Stress => LayoutAlgorithm::StressMajorization,
         }
-        | Graph::DiGraph {
-            id: _,
-            strict: _,
-            ref stmts,
-        } => stmts,
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Coloring {
+    /// Derive colors from the node ids and shape names
+    Hash,
+    /// Pick colors at random, reproducible with --seed
+    Random,
+    /// Ramp from blue to red with the PageRank of each node
+    Pagerank,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Highlight {
+    /// Minimum spanning tree, by edge weight
+    Mst,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Overflow {
+    /// Stop with an error
+    Error,
+    /// Keep the nodes with the most edges and the edges among them
+    Truncate,
+}
+
+/// Lays out a DOT or GraphML graph in 3D and streams it to a rerun viewer.
+#[derive(Parser)]
+struct Args {
+    /// DOT or GraphML file to read, optionally gzipped, or stdin when omitted
+    path: Option<PathBuf>,
+    /// TOML file with [layout] parameters and [colors], overridden by the
+    /// options below
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Print the effective config as TOML and exit, as a template for --config
+    #[arg(long)]
+    dump_config: bool,
+    /// Parse the graph and print its size, connected components and ignored
+    /// attributes, then exit without laying it out
+    #[arg(long, conflicts_with = "watch")]
+    check: bool,
+    /// Seed for reproducible layouts and colors
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Force model to lay out the graph with [default: legacy]
+    #[arg(long, value_enum)]
+    algorithm: Option<Algorithm>,
+    /// Strength of the pull towards the origin that keeps components together
+    /// [default: 0]
+    #[arg(long)]
+    gravity: Option<f32>,
+    /// Number of batches of iterations per dimension level, which is how
+    /// often the view updates [default: 10]
+    #[arg(long)]
+    outer_iters: Option<usize>,
+    /// Number of iterations per batch, which is how far the forces are
+    /// resolved between updates [default: 10]
+    #[arg(long)]
+    inner_iters: Option<usize>,
+    /// Lay out in 2 dimensions, for a quick flat layout with z = 0, or in 3
+    /// [default: 3]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(2..=3))]
+    dims: Option<u8>,
+    /// Stop the solve at this many dimensions instead of 3 [default: 3]
+    #[arg(long)]
+    final_dims: Option<usize>,
+    /// Show a solve that stops above 3D by its principal axes instead of its
+    /// first three coordinates
+    #[arg(long)]
+    pca: bool,
+    /// Show these three coordinates, given as i,j,k, as X, Y and Z instead of
+    /// the first three, to view other planes of a solve that stops above 3D
+    #[arg(long, value_name = "I,J,K", value_parser = parse_axes, conflicts_with = "pca")]
+    render_axes: Option<[usize; 3]>,
+    /// Only lay out the nodes with this attribute value, given as key=value,
+    /// and the edges among them. Repeat to require several values
+    #[arg(long, value_parser = parse_filter)]
+    filter: Vec<(String, String)>,
+    /// Largest number of nodes to lay out, see --on-overflow
+    #[arg(long)]
+    max_nodes: Option<usize>,
+    /// What to do with graphs that have more nodes than --max-nodes
+    #[arg(long, value_enum, default_value_t = Overflow::Error)]
+    on_overflow: Overflow,
+    /// Center the final layout and scale it to fit in a cube of this size,
+    /// instead of keeping the raw solver coordinates
+    #[arg(long)]
+    normalize: Option<f32>,
+    /// Write the graph with the computed positions as pos attributes to this file
+    #[arg(long)]
+    output_dot: Option<PathBuf>,
+    /// Write the node positions, labels, colors and edges as JSON to this file
+    #[arg(long)]
+    output_json: Option<PathBuf>,
+    /// Write the node positions and edges of the solver steps to numbered
+    /// JSON files in this directory, for rendering animations
+    #[arg(long)]
+    export_frames: Option<PathBuf>,
+    /// Only write every Nth solver step with --export-frames
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        requires = "export_frames"
+    )]
+    frame_stride: usize,
+    /// Write the nodes as vertices and the edges as lines to this .ply file,
+    /// or to an .obj file without colors
+    #[arg(long)]
+    export_mesh: Option<PathBuf>,
+    /// Only compute the layout and write exports, without connecting to rerun
+    #[arg(long)]
+    headless: bool,
+    /// Open a viewer window once the layout is solved instead of streaming to
+    /// a running rerun server
+    #[arg(long, conflicts_with = "headless")]
+    native: bool,
+    /// Outline the extent of the final layout with a box in the viewer
+    #[arg(long)]
+    show_bounds: bool,
+    /// Lay the graph out again whenever the file changes, starting from the
+    /// previous positions of the nodes that are still in it
+    #[arg(long, requires = "path", conflicts_with = "native")]
+    watch: bool,
+    /// Bend edges that run alongside each other into bundles in the final
+    /// layout. Bundled edges are drawn without arrowheads
+    #[arg(long)]
+    bundle: bool,
+    /// Only send the final layout to rerun instead of every solver step
+    #[arg(long)]
+    final_only: bool,
+    /// How to pick the colors of nodes without a color attribute
+    #[arg(long, value_enum, default_value_t = Coloring::Hash)]
+    coloring: Coloring,
+    /// Give the nodes a color for each distinct value of this attribute, and
+    /// gray if they lack it, in place of all other coloring
+    #[arg(long, value_name = "KEY", conflicts_with = "coloring")]
+    color_by: Option<String>,
+    /// Fade edges by how far they are from their rest length, so the most
+    /// strained edges stand out while the layout settles
+    #[arg(long, conflicts_with = "final_only")]
+    strain_alpha: bool,
+    /// Only send solver steps once the layout has been reduced to 3D
+    #[arg(long)]
+    skip_annealing: bool,
+    /// Start from the 2D pos attributes of a previous Graphviz layout and
+    /// only randomize the extra dimensions
+    #[arg(long)]
+    warm_start_2d: bool,
+    /// Draw a subset of the edges in a highlight color and the rest faintly
+    #[arg(long, value_enum)]
+    highlight: Option<Highlight>,
+    /// Highlight a shortest path between two nodes, following the edge
+    /// directions in digraphs, and draw the rest faintly
+    #[arg(
+        long = "path",
+        num_args = 2,
+        value_names = ["FROM", "TO"],
+        conflicts_with = "highlight"
+    )]
+    shortest_path: Option<Vec<String>>,
+    /// Draw the edges of a digraph that close a cycle in a separate color
+    #[arg(long)]
+    back_edges: bool,
+    /// Size nodes by their number of edges instead of by shape
+    #[arg(long)]
+    size_by_degree: bool,
+    /// Size nodes by their PageRank instead of by shape
+    #[arg(long, conflicts_with = "size_by_degree")]
+    size_by_pagerank: bool,
+    /// Probability of following an edge rather than jumping to a random node
+    /// when computing PageRank
+    #[arg(long, default_value_t = 0.85)]
+    pagerank_damping: f32,
+    /// Number of iterations when computing PageRank
+    #[arg(long, default_value_t = 50)]
+    pagerank_iterations: usize,
+    /// Print the edge lengths, edge crossings and nearest neighbor distances
+    /// of the final layout, and add them to --output-json. These take time
+    /// quadratic in the size of the graph to compute
+    #[arg(long)]
+    metrics: bool,
+    /// Print the progress of the solver to stderr
+    #[arg(long)]
+    verbose: bool,
+    /// Log all entities under this path, to show several graphs in one viewer
+    #[arg(long)]
+    entity_prefix: Option<String>,
+    /// Only show the part of node labels after the last '/'
+    #[arg(long)]
+    basename_labels: bool,
+}
+
+fn main() -> Result<(), GraphViz3dError> {
+    let args = Args::parse();
+
+    let mut config = match &args.config {
+        Some(path) => {
+            let config_error = |e: &dyn std::fmt::Display| {
+                GraphViz3dError::Config(format!("{}: {}", path.display(), e))
+            };
+            let text = std::fs::read_to_string(path).map_err(|e| config_error(&e))?;
+            toml::from_str::<Config>(&text).map_err(|e| config_error(&e))?
+        }
+        None => Config::default(),
     };
+    if args.seed.is_some() {
+        config.layout.seed = args.seed;
+    }
+    if let Some(algorithm) = args.algorithm {
+        config.layout.algorithm = algorithm.into();
+    }
+    if let Some(gravity) = args.gravity {
+        config.layout.gravity = gravity;
+    }
+    if let Some(outer_iters) = args.outer_iters {
+        config.layout.outer_iterations = outer_iters;
+    }
+    if let Some(inner_iters) = args.inner_iters {
+        config.layout.inner_iterations = inner_iters;
+    }
+    if let Some(final_dims) = args.final_dims {
+        config.layout.final_dims = final_dims;
+    }
+    if let Some(dims) = args.dims {
+        config.layout.flat = dims == 2;
+    }
+    config.layout.pca_projection |= args.pca;
+    if args.normalize.is_some() {
+        config.layout.normalize_size = args.normalize;
+    }
+    config.layout.warm_start_2d |= args.warm_start_2d;
+    config.layout.verbose |= args.verbose;
+    if args.dump_config {
+        let toml = toml::to_string(&config).map_err(|e| GraphViz3dError::Config(e.to_string()))?;
+        print!("{}", toml);
+        return Ok(());
+    }
+    let Config {
+        layout: params,
+        colors: mut color_scheme,
+    } = config;
+    let active_dims = params.active_dims();
+    if let Some(axis) = args
+        .render_axes
+        .into_iter()
+        .flatten()
+        .find(|&k| k >= active_dims)
+    {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "--render-axes {} is out of range, the solve stops at {} dimensions",
+                    axis, active_dims
+                ),
+            )
+            .exit();
+    }
+    color_scheme.reference_length = params.edge_length;
+    let mut rng = make_rng(params.seed);
 
-    let session = rerun::SessionBuilder::new("my_app").connect(rerun::default_server_addr());
+    let dot = match &args.path {
+        Some(path) => match std::fs::read(path)
+            .map_err(GraphViz3dError::from)
+            .and_then(decode_dot)
+        {
+            Ok(dot) => dot,
+            Err(e) => {
+                eprintln!("error: {}: {}", path.display(), e);
+                eprintln!("{}", Args::command().render_usage());
+                std::process::exit(1);
+            }
+        },
+        None => {
+            let mut bytes = Vec::new();
+            std::io::stdin().read_to_end(&mut bytes)?;
+            decode_dot(bytes)?
+        }
+    };
+    let graphs = parse_input(args.path.as_deref(), &dot)?;
+    if args.check {
+        check_graphs(&graphs);
+        return Ok(());
+    }
 
-    let nodes: HashMap<String, Node> = statements
-        .iter()
-        .filter_map(|s| match s {
-            Stmt::Node(n) => Some((node_id_to_string(&n.id), n.clone())),
-            _ => None,
-        })
-        .collect();
+    let mut session = if args.headless {
+        None
+    } else if args.native {
+        // Kept in memory until the viewer is opened below
+        Some(rerun::SessionBuilder::new("my_app").buffered())
+    } else {
+        Some(rerun::SessionBuilder::new("my_app").connect(rerun::default_server_addr()))
+    };
+    let mut previous = Vec::new();
+    lay_out_graphs(
+        &args,
+        &params,
+        &color_scheme,
+        &graphs,
+        &mut session,
+        &mut rng,
+        &mut previous,
+    )?;
 
-    let mut node_indices = HashMap::<String, usize>::new();
-    for key in nodes.keys() {
-        node_indices.insert(key.clone(), node_indices.len());
-    }
-    let node_indices = node_indices;
-    let num_points = node_indices.len();
-
-    let mut edges = Vec::<(NodeId, NodeId)>::new();
-    for s in statements {
-        match s {
-            Stmt::Edge(Edge {
-                ty: EdgeTy::Pair(Vertex::N(a), Vertex::N(b)),
-                attributes: _,
-            }) => edges.push((a.clone(), b.clone())),
-            Stmt::Edge(Edge {
-                ty: EdgeTy::Chain(chain),
-                attributes: _,
-            }) => {
-                for i in 0..chain.len() - 1 {
-                    if let (Vertex::N(a), Vertex::N(b)) = (&chain[i], &chain[i + 1]) {
-                        edges.push((a.clone(), b.clone()))
-                    }
-                }
+    if let (Some(session), true) = (&session, args.native) {
+        rerun::native_viewer::show(session).map_err(|e| GraphViz3dError::Render(Box::new(e)))?;
+    }
+
+    if let (Some(path), true) = (&args.path, args.watch) {
+        println!("Watching {} for changes", path.display());
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut last_modified = modified(path);
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            // Editors may briefly remove the file while saving it
+            let Some(time) = modified(path) else {
+                continue;
+            };
+            if Some(time) == last_modified {
+                continue;
+            }
+            last_modified = Some(time);
+            let result = std::fs::read(path)
+                .map_err(GraphViz3dError::from)
+                .and_then(decode_dot)
+                .and_then(|dot| parse_input(Some(path), &dot))
+                .and_then(|graphs| {
+                    lay_out_graphs(
+                        &args,
+                        &params,
+                        &color_scheme,
+                        &graphs,
+                        &mut session,
+                        &mut rng,
+                        &mut previous,
+                    )
+                });
+            // Keep watching, the next save may fix the file
+            if let Err(e) = result {
+                eprintln!("error: {}: {}", path.display(), e);
             }
-            _ => (),
         }
     }
-    let edges = edges;
 
-    let edges_indices = edges
-        .iter()
-        .map(|(a, b)| {
-            (
-                *node_indices.get(&node_id_to_string(a)).unwrap(),
-                *node_indices.get(&node_id_to_string(b)).unwrap(),
-            )
-        })
-        .collect::<Vec<_>>();
+    Ok(())
+}
 
-    let mut num_outgoing = vec![0; num_points];
-    let mut num_incoming = vec![0; num_points];
-    for &(i, j) in &edges_indices {
-        num_outgoing[i] += 1;
-        num_incoming[j] += 1;
+/// How often `--watch` checks whether the file has changed.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Parses the graphs in `dot`, which is GraphML rather than DOT if `path` or
+/// the text says so.
+fn parse_input(path: Option<&Path>, dot: &str) -> Result<Vec<Graph>, GraphViz3dError> {
+    let graphs = if is_graphml(path, dot) {
+        parse_graphml(dot)?
+    } else {
+        parse_graphs(dot)?
+    };
+    if graphs.len() > 1 {
+        println!("Found {} graphs", graphs.len());
     }
-    let num_outgoing = num_outgoing;
-    let num_incoming = num_incoming;
+    Ok(graphs)
+}
+
+/// Where the last layout of a graph left off, so that `--watch` can continue
+/// from it.
+#[derive(Default)]
+struct Previous {
+    /// Final position of every node, keyed by [`node_key`].
+    positions: HashMap<String, Vec<f32>>,
+    /// Step after the last frame, so that each layout follows the previous
+    /// one on the timeline.
+    next_step: i64,
+}
 
-    let mut colors = Vec::with_capacity(node_indices.len());
-    let mut labels = Vec::with_capacity(node_indices.len());
+/// Lays out every graph in the input. `previous` holds where the last layout
+/// of each graph left off and is updated with the new layouts.
+fn lay_out_graphs(
+    args: &Args,
+    params: &LayoutParams,
+    color_scheme: &ColorScheme,
+    graphs: &[Graph],
+    session: &mut Option<Session>,
+    rng: &mut StdRng,
+    previous: &mut Vec<Previous>,
+) -> Result<(), GraphViz3dError> {
+    previous.resize_with(graphs.len(), Previous::default);
+    for (i, (g, previous)) in graphs.iter().zip(previous.iter_mut()).enumerate() {
+        let filtered;
+        let g = if args.filter.is_empty() {
+            g
+        } else {
+            filtered = filter_nodes(g, &args.filter);
+            println!(
+                "Kept {} of {} nodes matching the filters",
+                flatten_graph(&filtered).nodes.len(),
+                flatten_graph(g).nodes.len()
+            );
+            &filtered
+        };
+        // Separate the graphs in the viewer and in the exports
+        let index = (graphs.len() > 1).then_some(i);
+        lay_out_graph(args, params, color_scheme, g, session, rng, index, previous)?;
+    }
+    Ok(())
+}
+
+/// Lays out one graph and sends it to the viewer and exports. `index` is the
+/// position of the graph in the input when it holds more than one. The layout
+/// starts from `previous` if it has any positions.
+#[allow(clippy::too_many_arguments)]
+fn lay_out_graph(
+    args: &Args,
+    params: &LayoutParams,
+    color_scheme: &ColorScheme,
+    g: &Graph,
+    session: &mut Option<Session>,
+    rng: &mut StdRng,
+    index: Option<usize>,
+    previous: &mut Previous,
+) -> Result<(), GraphViz3dError> {
+    let Flattened {
+        nodes,
+        edges: edges_indices,
+        edge_attributes,
+    } = flatten_graph(g);
+    let num_points = nodes.len();
+    if num_points == 0 {
+        println!("The graph has no nodes, so there is nothing to lay out");
+        return Ok(());
+    }
+    if let Some(max) = args.max_nodes.filter(|&max| num_points > max) {
+        match args.on_overflow {
+            Overflow::Error => {
+                return Err(GraphViz3dError::TooManyNodes {
+                    nodes: num_points,
+                    max,
+                })
+            }
+            Overflow::Truncate => {
+                let truncated = keep_highest_degree(g, max);
+                let Flattened {
+                    nodes: kept_nodes,
+                    edges: kept_edges,
+                    ..
+                } = flatten_graph(&truncated);
+                println!(
+                    "Dropped {} of {} nodes and {} of {} edges to stay within --max-nodes",
+                    num_points - kept_nodes.len(),
+                    num_points,
+                    edges_indices.len() - kept_edges.len(),
+                    edges_indices.len()
+                );
+                return lay_out_graph(
+                    args,
+                    params,
+                    color_scheme,
+                    &truncated,
+                    session,
+                    rng,
+                    index,
+                    previous,
+                );
+            }
+        }
+    }
+
+    if params.algorithm == LayoutAlgorithm::StressMajorization && num_points > STRESS_WARN_NODES {
+        eprintln!(
+            "warning: stress majorization of {} nodes needs {} MB for distances",
+            num_points,
+            GraphDistances::memory_size(num_points) / 1_000_000
+        );
+    }
+
+    let use_pagerank = args.size_by_pagerank || matches!(args.coloring, Coloring::Pagerank);
+    let ranks = use_pagerank.then(|| {
+        pagerank(
+            num_points,
+            &edges_indices,
+            is_directed(g),
+            args.pagerank_damping,
+            args.pagerank_iterations,
+        )
+    });
+    let max_rank = ranks.iter().flatten().copied().fold(0.0, f32::max);
+
+    let mut colors = Vec::with_capacity(num_points);
+    let mut labels = Vec::with_capacity(num_points);
+    let mut styles = Vec::with_capacity(num_points);
+    let mut sizes = Vec::with_capacity(num_points);
+    let mut unknown_colors = BTreeSet::new();
+    let mut legend = BTreeMap::new();
+    let graph_name = graph_id(g).unwrap_or_default();
+    let graph_escapes = LabelEscapes {
+        graph: &graph_name,
+        ..Default::default()
+    };
     {
         let mut color_map = HashMap::new();
-        for node in nodes.values() {
-            let mut color = ColorRGBA::from_rgb(
-                rng.gen_range(0..255),
-                rng.gen_range(0..255),
-                rng.gen_range(0..255),
-            );
+        for (i, node) in nodes.iter().enumerate() {
+            let mut color = match args.coloring {
+                Coloring::Hash => {
+                    let [r, g, b] = hash_color(id_value(&node.id.0));
+                    ColorRGBA::from_rgb(r, g, b)
+                }
+                Coloring::Random => ColorRGBA::from_rgb(
+                    rng.gen_range(0..255),
+                    rng.gen_range(0..255),
+                    rng.gen_range(0..255),
+                ),
+                Coloring::Pagerank => {
+                    let rank = ranks.as_ref().map_or(0.0, |ranks| ranks[i]);
+                    pagerank_color(rank, max_rank)
+                }
+            };
             let mut label = Label(node.id.0.to_string());
+            let mut explicit_color = None;
+            let mut style = DEFAULT_STYLE;
+            let mut size = None::<f32>;
+            let scheme = attribute_value(&node.attributes, "colorscheme");
             for a in &node.attributes {
                 let a0 = a.0.to_string();
                 let a1 = a.1.to_string();
                 match a0.as_str() {
                     "label" => {
-                        let s = match &a.1 {
-                            Id::Html(s) => s,
-                            Id::Escaped(s) => s,
-                            Id::Plain(s) => s,
-                            Id::Anonymous(s) => s,
+                        let escapes = LabelEscapes {
+                            node: id_value(&node.id.0),
+                            ..graph_escapes
                         };
-                        let start = match s.rfind('/') {
-                            Some(i) => i + 1,
-                            None => match s.find('"') {
-                                Some(i) => i + 1,
-                                None => 0,
-                            },
-                        };
-                        let end = s.rfind('"').unwrap_or(s.len());
-                        label = Label(s[start..end].to_string());
+                        label = Label(label_text(&a.1, args.basename_labels, &escapes));
                     }
-                    "shape" => match color_map.get(&a1) {
-                        Some(&c) => {
-                            color = c;
+                    "shape" => {
+                        style = shape_style(id_value(&a.1));
+                        match (style.color, color_map.get(&a1)) {
+                            (Some([r, g, b]), _) => color = ColorRGBA::from_rgb(r, g, b),
+                            // The ramp colors each node by itself
+                            (None, _) if matches!(args.coloring, Coloring::Pagerank) => (),
+                            (None, Some(&c)) => color = c,
+                            (None, None) => {
+                                if let Coloring::Hash = args.coloring {
+                                    let [r, g, b] = hash_color(id_value(&a.1));
+                                    color = ColorRGBA::from_rgb(r, g, b);
+                                }
+                                color_map.insert(a1, color);
+                            }
+                        }
+                        legend.insert(id_value(&a.1).to_string(), color);
+                    }
+                    "color" => match parse_color_in(id_value(&a.1), scheme) {
+                        Some([r, g, b, alpha]) => {
+                            explicit_color =
+                                Some(ColorRGBA::from_unmultiplied_rgba(r, g, b, alpha));
                         }
                         None => {
-                            color_map.insert(a1, color);
+                            let color = id_value(&a.1);
+                            unknown_colors.insert(match scheme {
+                                Some(scheme) => format!("{} in {}", color, scheme),
+                                None => color.to_string(),
+                            });
                         }
                     },
+                    "width" | "height" => {
+                        if let Ok(inches) = id_value(&a.1).parse::<f32>() {
+                            size = Some(size.map_or(inches, |size| size.max(inches)));
+                        }
+                    }
                     _ => (),
                 }
             }
-            colors.push(color);
+            colors.push(explicit_color.unwrap_or(color));
             labels.push(label);
+            styles.push(style);
+            sizes.push(size);
         }
         println!("color_map:\n{:?}", color_map);
     }
-    let colors = colors;
-    let labels = labels;
+    if let Some(key) = &args.color_by {
+        let values = nodes
+            .iter()
+            .map(|node| attribute_value(&node.attributes, key))
+            .collect::<Vec<_>>();
+        let distinct = values.iter().flatten().copied().collect::<BTreeSet<_>>();
+        legend = distinct
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| {
+                let [r, g, b] = spread_color(i, distinct.len());
+                (value.to_string(), ColorRGBA::from_rgb(r, g, b))
+            })
+            .collect();
+        for (color, value) in colors.iter_mut().zip(&values) {
+            *color = match value {
+                Some(value) => legend[*value],
+                None => ColorRGBA::from_rgb(128, 128, 128),
+            };
+        }
+        println!("Colored {} values of {}", distinct.len(), key);
+    }
     assert_eq!(colors.len(), num_points);
     assert_eq!(labels.len(), num_points);
+    assert_eq!(styles.len(), num_points);
+    if args.size_by_degree {
+        let degrees = degrees(num_points, &edges_indices);
+        let radii = degree_radii(&degrees, params.min_radius, params.max_radius);
+        for (style, radius) in styles.iter_mut().zip(radii) {
+            style.radius = radius;
+        }
+    }
+    if let (Some(ranks), true) = (&ranks, args.size_by_pagerank) {
+        let radii = score_radii(ranks, params.min_radius, params.max_radius);
+        for (style, radius) in styles.iter_mut().zip(radii) {
+            style.radius = radius;
+        }
+    }
+    // Sizes given in the file take precedence
+    for (style, size) in styles.iter_mut().zip(&sizes) {
+        if let Some(inches) = size {
+            style.radius = inches * params.radius_per_inch;
+        }
+    }
+    let edge_labels = edge_attributes
+        .iter()
+        .enumerate()
+        .filter_map(|(e, attributes)| {
+            let Attribute(_, label) = attributes
+                .iter()
+                .rev()
+                .find(|a| id_value(&a.0) == "label")?;
+            Some((
+                e,
+                Label(label_text(label, args.basename_labels, &graph_escapes)),
+            ))
+        })
+        .collect();
+    let edge_styles = edge_attributes
+        .iter()
+        .map(|attributes| LineStyle::parse(attribute_value(attributes, "style")))
+        .collect();
+    let mut highlighted = args.highlight.map(|highlight| match highlight {
+        Highlight::Mst => {
+            let weights = edge_attributes
+                .iter()
+                .map(|attributes| parse_weight(attribute_value(attributes, "weight")))
+                .collect::<Vec<_>>();
+            minimum_spanning_tree(num_points, &edges_indices, &weights)
+        }
+    });
+    let back_edges = (args.back_edges && is_directed(g)).then(|| {
+        let back = back_edges(num_points, &edges_indices);
+        println!(
+            "{} of {} edges close a cycle",
+            back.iter().filter(|&&b| b).count(),
+            back.len()
+        );
+        back
+    });
+    if let Some([from, to]) = args.shortest_path.as_deref() {
+        let find = |name: &String| {
+            nodes
+                .iter()
+                .position(|node| id_value(&node.id.0) == name)
+                .ok_or_else(|| GraphViz3dError::MissingNode(name.clone()))
+        };
+        let (path, path_edges) = shortest_path(
+            num_points,
+            &edges_indices,
+            find(from)?,
+            find(to)?,
+            is_directed(g),
+        )
+        .ok_or_else(|| GraphViz3dError::NoPath(from.clone(), to.clone()))?;
+        let names = path
+            .iter()
+            .map(|&i| id_value(&nodes[i].id.0))
+            .collect::<Vec<_>>();
+        println!(
+            "Shortest path from {} to {} has {} edges: {}",
+            from,
+            to,
+            path_edges.len(),
+            names.join(" -> ")
+        );
+        let mut on_path = vec![false; edges_indices.len()];
+        for e in path_edges {
+            on_path[e] = true;
+        }
+        highlighted = Some(on_path);
+        for (i, color) in colors.iter_mut().enumerate() {
+            *color = if path.contains(&i) {
+                color_scheme.highlight_color
+            } else {
+                color_scheme.faint(*color)
+            };
+        }
+    }
+    let clusters = cluster_boxes(g, &nodes);
+    // Named after the input file when the graph has no id
+    let name = graph_id(g).or_else(|| {
+        let file_name = args.path.as_deref()?.file_name()?;
+        Some(file_name.to_string_lossy().into_owned())
+    });
+    let title = [name, graph_label(g)]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("\n");
+    let scene = Scene {
+        entity_prefix: match (&args.entity_prefix, index) {
+            (Some(prefix), Some(i)) => Some(format!("{}/graph_{}", prefix, i)),
+            (None, Some(i)) => Some(format!("graph_{}", i)),
+            (prefix, None) => prefix.clone(),
+        },
+        directed: is_directed(g),
+        edges_indices,
+        colors,
+        labels,
+        styles,
+        edge_labels,
+        edge_styles,
+        highlighted,
+        back_edges,
+        strain_alpha: args.strain_alpha,
+        render_axes: args.render_axes.unwrap_or([0, 1, 2]),
+        clusters,
+        legend: legend.into_iter().collect(),
+        title: (!title.is_empty()).then_some(Label(title)),
+        color_scheme: color_scheme.clone(),
+    };
 
-    let edge_strength = 0.1;
-    let edge_length = 1.0;
-    let node_repelling_strength = 0.1;
-    let node_repelling_distance = 2.0;
-    let float_strength = 0.02;
-    let float_distance = 2.0;
-    let node_degree_strength = 0.01;
-
-    // Init points with random values in many dimensions
-    let mut points = vec![[0.0; MAX_DIMS]; num_points];
-    for i in 0..num_points {
-        for v in &mut points[i] {
-            *v = rng.gen_range(-1.0..1.0);
+    let frames_dir = args
+        .export_frames
+        .as_ref()
+        .map(|dir| indexed_path(dir, index));
+    if let Some(dir) = &frames_dir {
+        std::fs::create_dir_all(dir).map_err(|source| GraphViz3dError::Write {
+            path: dir.clone(),
+            source,
+        })?;
+    }
+    let mut frames_result = Ok(());
+    let mut num_steps = 0;
+    let mut result = Ok(());
+    let first_step = previous.next_step;
+    let on_step = |points: &[f32], dims: usize, iterations: usize| {
+        if let (Some(dir), true) = (&frames_dir, frames_result.is_ok()) {
+            if num_steps % args.frame_stride == 0 {
+                let frame = FrameExport::new(
+                    points,
+                    params.max_dims,
+                    dims,
+                    iterations,
+                    &scene.edges_indices,
+                );
+                let path = dir.join(FrameExport::file_name(num_steps / args.frame_stride));
+                frames_result = write_file(&path, frame.to_json());
+            }
+            num_steps += 1;
         }
-        points[i][1] = num_incoming[i] as f32;
-    }
-
-    // Gradually reduce the number of dimensions while solving the constraints
-    for dims in (3..MAX_DIMS).rev() {
-        for _ in 0..10 {
-            for _ in 0..10 {
-                // Move parents upwards and children downwards
-                for &(i, j) in &edges_indices {
-                    let p1 = &points[i];
-                    let p2 = &points[j];
-                    let dz = p1[2] - p2[2];
-                    if dz < float_distance {
-                        points[i][2] += float_strength;
-                        points[j][2] -= float_strength;
-                    }
-                }
+        if let (Some(session), false) = (session.as_ref(), args.final_only) {
+            if result.is_ok() && (dims <= 3 || !args.skip_annealing) {
+                let step = first_step + iterations as i64;
+                result = send_frame(session, &scene, points, dims, params, step, None);
+            }
+        }
+    };
+    let layout = if previous.positions.is_empty() {
+        layout_streaming(g, params, on_step)
+    } else {
+        relayout_streaming(g, params, &previous.positions, on_step)
+    };
+    drop_session_on_error(session, result);
+    frames_result?;
+    let last_step = first_step + layout.iterations as i64;
+    previous.positions = nodes
+        .iter()
+        .zip(layout.points.chunks(params.max_dims))
+        .map(|(node, p)| (node_key(&node.id), p.to_vec()))
+        .collect();
+    previous.next_step = last_step + 1;
+    if let (Some(viewer), true) = (session.as_ref(), args.final_only || args.bundle) {
+        // Bundling is too slow to redo for every step, so the final layout
+        // is sent again with the bundled edges
+        let bundles = args.bundle.then(|| {
+            let positions = select_axes(&layout.points, params.max_dims, scene.render_axes)
+                .chunks(3)
+                .map(|p| [p[0], p[1], p[2]])
+                .collect::<Vec<_>>();
+            bundle_edges(&positions, &scene.edges_indices, &BundleParams::default())
+        });
+        let result = send_frame(
+            viewer,
+            &scene,
+            &layout.points,
+            3,
+            params,
+            last_step,
+            bundles.as_deref(),
+        );
+        drop_session_on_error(session, result);
+    }
+    println!("Solved in {} iterations", layout.iterations);
+    let metrics = args
+        .metrics
+        .then(|| layout_metrics(&layout.points, params.max_dims, &scene.edges_indices));
+    if let Some(metrics) = &metrics {
+        println!(
+            "Edge length {:.3} ± {:.3}, {} crossings in XY, nearest neighbor at {:.3} on average",
+            metrics.mean_edge_length,
+            metrics.edge_length_stddev,
+            metrics.edge_crossings,
+            metrics.mean_nearest_neighbor_distance
+        );
+    }
+    let bounds = bounding_box(&layout.points, params.max_dims);
+    println!(
+        "Bounds from {:?} to {:?}, center {:?}, size {:?}",
+        bounds.min,
+        bounds.max,
+        bounds.center(),
+        bounds.size()
+    );
+    if let (Some(viewer), true) = (session.as_ref(), args.show_bounds) {
+        let shown = select_axes(&layout.points, params.max_dims, scene.render_axes);
+        let bounds = bounding_box(&shown, 3);
+        let result = send_bounds(viewer, &scene.entity_prefix, &bounds, last_step);
+        drop_session_on_error(session, result);
+    }
 
-                // Move nodes with many edges towards y+
-                for i in 0..num_points {
-                    points[i][1] += node_degree_strength * (num_incoming[i] as f32 - points[i][1])
-                }
+    if let Some(path) = &args.output_dot {
+        let path = indexed_path(path, index);
+        write_file(&path, export_dot(g, &layout.points, params.max_dims))?;
+    }
+    let colors = scene
+        .colors
+        .iter()
+        .map(|c| {
+            let [r, g, b, _] = c.to_array();
+            [r, g, b]
+        })
+        .collect::<Vec<_>>();
+    if let Some(path) = &args.output_json {
+        let path = indexed_path(path, index);
+        let labels = scene.labels.iter().map(|l| l.0.clone()).collect::<Vec<_>>();
+        let export = LayoutExport::new(
+            &nodes,
+            &scene.edges_indices,
+            &layout.points,
+            params.max_dims,
+            &labels,
+            &colors,
+        );
+        let export = match &ranks {
+            Some(ranks) => export.with_pagerank(ranks),
+            None => export,
+        };
+        let export = match metrics {
+            Some(metrics) => export.with_metrics(metrics),
+            None => export,
+        };
+        write_file(&path, export.to_json())?;
+    }
+    if let Some(path) = &args.export_mesh {
+        let path = indexed_path(path, index);
+        let is_obj = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("obj"));
+        let mesh = if is_obj {
+            export_obj(&layout.points, params.max_dims, &scene.edges_indices)
+        } else {
+            export_ply(
+                &layout.points,
+                params.max_dims,
+                &colors,
+                &scene.edges_indices,
+            )
+        };
+        write_file(&path, mesh)?;
+    }
 
-                // Move nodes away from each other
-                for i in 0..num_points {
-                    for j in i + 1..num_points {
-                        let length = points_distance(&points, i, j, dims);
-                        if length < node_repelling_distance {
-                            let c = node_repelling_distance - length;
-                            let d = c.min(node_repelling_strength) * 0.5 / length.max(0.001);
-                            for k in 0..dims {
-                                let u = (points[j][k] - points[i][k]) * d;
-                                points[i][k] -= u;
-                                points[j][k] += u;
-                            }
-                        }
-                    }
-                }
+    if !unknown_colors.is_empty() {
+        let summary = unknown_colors.into_iter().collect::<Vec<_>>();
+        eprintln!(
+            "warning: unknown colors, drawn with the default coloring: {}",
+            summary.join(", ")
+        );
+    }
+    if let Some(summary) = ignored_attributes(nodes.iter().map(|n| n.attributes.as_slice())) {
+        println!("Ignored node attributes: {}", summary);
+    }
 
-                // Move nodes to satisfy edge length
-                for &(i, j) in &edges_indices {
-                    let length = points_distance(&points, i, j, dims);
-                    let c = length - edge_length;
-                    let d = edge_strength * c * -0.5 / length.max(0.001);
-                    for k in 0..dims {
-                        let u = (points[j][k] - points[i][k]) * d;
-                        points[i][k] -= u;
-                        points[j][k] += u;
-                    }
-                }
-            }
-            for i in 0..num_points {
-                let point = Point3D {
-                    x: points[i][0],
-                    y: points[i][1],
-                    z: points[i][2],
-                };
-                MsgSender::new(format!("nodes/{}", &labels[i].0))
-                    .with_component(&[point])?
-                    .with_component(&[colors[i].clone()])?
-                    .with_component(&[labels[i].clone()])?
-                    .with_splat(Radius(0.05))?
-                    .send(&session)?;
+    Ok(())
+}
+
+/// Node attributes that change how the graph is drawn or laid out. `pos` and
+/// `pin` seed and pin the initial positions and `colorscheme` is read
+/// together with `color`.
+const NODE_ATTRIBUTES: &[&str] = &[
+    "label",
+    "shape",
+    "color",
+    "colorscheme",
+    "width",
+    "height",
+    "pos",
+    "pin",
+];
+
+/// Returns the attributes of the nodes that are not in [`NODE_ATTRIBUTES`],
+/// with the number of nodes that have each, or `None` if there are none.
+fn ignored_attributes<'a>(nodes: impl IntoIterator<Item = &'a [Attribute]>) -> Option<String> {
+    let mut ignored = BTreeMap::<String, usize>::new();
+    for a in nodes.into_iter().flatten() {
+        let key = a.0.to_string();
+        if !NODE_ATTRIBUTES.contains(&key.as_str()) {
+            *ignored.entry(key).or_default() += 1;
+        }
+    }
+    let summary = ignored
+        .iter()
+        .map(|(key, count)| format!("{} ({})", key, count))
+        .collect::<Vec<_>>();
+    (!summary.is_empty()).then(|| summary.join(", "))
+}
+
+/// Prints a summary of each graph for `--check`.
+fn check_graphs(graphs: &[Graph]) {
+    for (i, g) in graphs.iter().enumerate() {
+        let flat = FlatGraph::new(g);
+        let (num_components, _) = connected_components(flat.nodes.len(), &flat.edges_indices());
+        let name = graph_id(g).unwrap_or_else(|| format!("Graph {}", i + 1));
+        println!(
+            "{}: {} nodes, {} edges, {} connected components",
+            name,
+            flat.nodes.len(),
+            flat.edges.len(),
+            num_components
+        );
+        if let Some(summary) =
+            ignored_attributes(flat.nodes.iter().map(|n| n.attributes.as_slice()))
+        {
+            println!("Ignored node attributes: {}", summary);
+        }
+    }
+}
+
+/// Returns the color of a node with PageRank `rank` on a ramp from blue for
+/// the lowest rank to red for `max_rank`.
+fn pagerank_color(rank: f32, max_rank: f32) -> ColorRGBA {
+    let t = if max_rank > 0.0 {
+        (rank / max_rank).sqrt()
+    } else {
+        0.0
+    };
+    ColorRGBA::from_rgb(40, 80, 255).lerp(ColorRGBA::from_rgb(255, 50, 0), t)
+}
+
+/// Stops sending to the viewer if `result` is an error, so that the layout and
+/// the exports are not lost when the viewer is unavailable.
+fn drop_session_on_error(
+    session: &mut Option<Session>,
+    result: Result<(), Box<dyn std::error::Error + Send + Sync>>,
+) {
+    if let Err(e) = result {
+        eprintln!(
+            "warning: {}, continuing without the viewer",
+            GraphViz3dError::Render(e)
+        );
+        *session = None;
+    }
+}
+
+/// Parses a `--render-axes` value of the form `i,j,k`, three different
+/// coordinate indices.
+fn parse_axes(axes: &str) -> Result<[usize; 3], String> {
+    let parsed = axes
+        .split(',')
+        .map(|k| k.trim().parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .and_then(|parsed| <[usize; 3]>::try_from(parsed).ok())
+        .ok_or_else(|| format!("expected three indices i,j,k, got `{}`", axes))?;
+    let [i, j, k] = parsed;
+    if i == j || j == k || i == k {
+        return Err(format!("expected three different indices, got `{}`", axes));
+    }
+    Ok(parsed)
+}
+
+/// Parses a `--filter` value of the form `key=value`.
+fn parse_filter(filter: &str) -> Result<(String, String), String> {
+    let (key, value) = filter
+        .split_once('=')
+        .ok_or_else(|| format!("expected key=value, got `{}`", filter))?;
+    Ok((key.trim().to_string(), value.trim().to_string()))
+}
+
+/// Writes `contents` to the output file at `path`.
+fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), GraphViz3dError> {
+    std::fs::write(path, contents).map_err(|source| GraphViz3dError::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Returns `path` with `_{index}` added to the file stem, if given.
+fn indexed_path(path: &Path, index: Option<usize>) -> PathBuf {
+    match index {
+        Some(i) => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let name = match path.extension() {
+                Some(extension) => format!("{}_{}.{}", stem, i, extension.to_string_lossy()),
+                None => format!("{}_{}", stem, i),
+            };
+            path.with_file_name(name)
+        }
+        None => path.to_path_buf(),
+    }
+}
+
+/// Per-node and per-edge data that stays fixed while the layout is solved.
+struct Scene {
+    /// Path that all entities are logged under, if any.
+    entity_prefix: Option<String>,
+    /// Draw edges as arrows rather than plain line segments.
+    directed: bool,
+    edges_indices: Vec<(usize, usize)>,
+    colors: Vec<ColorRGBA>,
+    labels: Vec<Label>,
+    styles: Vec<ShapeStyle>,
+    /// Index into `edges_indices` and label of every edge that has one.
+    edge_labels: Vec<(usize, Label)>,
+    edge_styles: Vec<LineStyle>,
+    /// Whether each edge is highlighted, when highlighting.
+    highlighted: Option<Vec<bool>>,
+    /// Whether each edge closes a cycle, with `--back-edges`.
+    back_edges: Option<Vec<bool>>,
+    /// Fade each edge by its strain in every frame.
+    strain_alpha: bool,
+    /// Coordinates shown as X, Y and Z.
+    render_axes: [usize; 3],
+    clusters: Vec<ClusterBox>,
+    /// Shape names, or values of the `--color-by` attribute, and the color of
+    /// their nodes, sorted by name.
+    legend: Vec<(String, ColorRGBA)>,
+    /// Graph id, or file name, and graph label, shown above the layout.
+    title: Option<Label>,
+    color_scheme: ColorScheme,
+}
+
+/// A cluster subgraph drawn as a box around its nodes.
+struct ClusterBox {
+    /// Entity path, nested under the path of the enclosing cluster.
+    path: String,
+    nodes: Vec<usize>,
+    /// Margin around the nodes, larger for clusters that enclose other
+    /// clusters so nested boxes do not overlap.
+    padding: f32,
+    label: Option<Label>,
+    color: ColorRGBA,
+}
+
+const CLUSTER_PADDING: f32 = 0.2;
+
+fn cluster_boxes(graph: &Graph, nodes: &[Node]) -> Vec<ClusterBox> {
+    let clusters = clusters(graph, nodes);
+    // Parents come before their nested clusters, so walking backwards sees
+    // every child before its parent.
+    let mut heights = vec![0; clusters.len()];
+    for (i, cluster) in clusters.iter().enumerate().rev() {
+        if let Some(parent) = cluster.parent {
+            heights[parent] = heights[parent].max(heights[i] + 1);
+        }
+    }
+    let mut paths: Vec<String> = Vec::with_capacity(clusters.len());
+    for cluster in &clusters {
+        let path = match cluster.parent {
+            Some(parent) => format!("{}/{}", paths[parent], cluster.name),
+            None => format!("clusters/{}", cluster.name),
+        };
+        paths.push(path);
+    }
+    clusters
+        .into_iter()
+        .zip(paths)
+        .zip(heights)
+        .map(|((cluster, path), height)| ClusterBox {
+            path,
+            nodes: cluster.nodes,
+            padding: CLUSTER_PADDING * (height + 1) as f32,
+            label: attribute_value(&cluster.attributes, "label").map(|l| Label(l.to_string())),
+            color: attribute_value(&cluster.attributes, "color")
+                .and_then(|color| {
+                    parse_color_in(color, attribute_value(&cluster.attributes, "colorscheme"))
+                })
+                .map(|[r, g, b, a]| ColorRGBA::from_unmultiplied_rgba(r, g, b, a))
+                .unwrap_or(ColorRGBA::from_rgb(128, 128, 128)),
+        })
+        .collect()
+}
+
+/// Sends the nodes, edges, clusters and legend at `points` to the viewer.
+/// Edges follow their polyline in `bundles`, if given, rather than being
+/// straight.
+fn send_frame(
+    session: &Session,
+    scene: &Scene,
+    points: &[f32],
+    dims: usize,
+    params: &LayoutParams,
+    step: i64,
+    bundles: Option<&[Vec<[f32; 3]>]>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let timeline = Timeline::new_sequence("iteration");
+    let Scene {
+        entity_prefix,
+        directed,
+        edges_indices,
+        colors,
+        labels,
+        styles,
+        edge_labels,
+        edge_styles,
+        highlighted,
+        back_edges,
+        strain_alpha,
+        render_axes,
+        clusters,
+        legend,
+        title,
+        color_scheme,
+    } = scene;
+    // Edges are measured in every active dimension, not just the shown ones
+    let lengths = edges_indices
+        .iter()
+        .map(|&(i, j)| points_distance(points, params.max_dims, i, j, dims))
+        .collect::<Vec<_>>();
+    let points = &select_axes(points, params.max_dims, *render_axes);
+    let stride = 3;
+    // Every node is an instance of its glyph's batch, so nodes that share a
+    // label are still separate points
+    let [spheres, boxes] = node_batches(points, stride, styles, colors, labels);
+    let positions = spheres
+        .positions
+        .iter()
+        .map(|&[x, y, z]| Point3D { x, y, z })
+        .collect::<Vec<_>>();
+    let radii = spheres.radii.iter().map(|&r| Radius(r)).collect::<Vec<_>>();
+    MsgSender::new(entity_path(entity_prefix, "nodes"))
+        .with_time(timeline, step)
+        .with_component(&positions)?
+        .with_component(&radii)?
+        .with_component(&spheres.colors)?
+        .with_component(&spheres.labels)?
+        .send(session)?;
+    if !boxes.positions.is_empty() {
+        let positions = boxes
+            .positions
+            .iter()
+            .map(|&[x, y, z]| Vec3D::new(x, y, z))
+            .collect::<Vec<_>>();
+        let sizes = boxes
+            .radii
+            .iter()
+            .map(|&r| Box3D::new(r, r, r))
+            .collect::<Vec<_>>();
+        MsgSender::new(entity_path(entity_prefix, "node_boxes"))
+            .with_time(timeline, step)
+            .with_component(&sizes)?
+            .with_component(&positions)?
+            .with_component(&boxes.colors)?
+            .with_component(&boxes.labels)?
+            .send(session)?;
+    }
+
+    for cluster in clusters {
+        if cluster.nodes.is_empty() {
+            continue;
+        }
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for &i in &cluster.nodes {
+            let p = &points[i * stride..];
+            for k in 0..3 {
+                min[k] = min[k].min(p[k] - styles[i].radius);
+                max[k] = max[k].max(p[k] + styles[i].radius);
             }
+        }
+        let half_size = |k: usize| 0.5 * (max[k] - min[k]) + cluster.padding;
+        let center = |k: usize| 0.5 * (min[k] + max[k]);
+        let mut sender = MsgSender::new(entity_path(entity_prefix, &cluster.path))
+            .with_time(timeline, step)
+            .with_component(&[Box3D::new(half_size(0), half_size(1), half_size(2))])?
+            .with_component(&[Vec3D::new(center(0), center(1), center(2))])?
+            .with_component(&[cluster.color])?;
+        if let Some(label) = &cluster.label {
+            sender = sender.with_component(std::slice::from_ref(label))?;
+        }
+        sender.send(session)?;
+    }
 
-            let mut arrows = Vec::with_capacity(edges_indices.len());
-            let mut arrow_colors = Vec::with_capacity(edges_indices.len());
-            for &(i, j) in &edges_indices {
-                let length = points_distance(&points, i, j, dims);
-                let p1 = &points[i];
-                let p2 = &points[j];
-                arrows.push(Arrow3D {
-                    origin: [p1[0], p1[1], p1[2]].into(),
-                    vector: [p2[0] - p1[0], p2[1] - p1[1], p2[2] - p1[2]].into(),
-                });
-                arrow_colors.push(if length < edge_length {
-                    let t = ((edge_length - length) / 0.5).clamp(0.0, 1.0);
-                    ColorRGBA::from_rgb(0.lerp(255, t), 255.lerp(0, t), 0)
+    let mut arrows = Vec::with_capacity(edges_indices.len());
+    let mut segments = Vec::new();
+    let mut arrow_colors = Vec::with_capacity(edges_indices.len());
+    let mut loops = Vec::new();
+    for (e, &(i, j)) in edges_indices.iter().enumerate() {
+        if i == j {
+            let p = &points[i * stride..];
+            loops.push(self_loop([p[0], p[1], p[2]], 2.0 * styles[i].radius));
+            continue;
+        }
+        let length = lengths[e];
+        let p1 = &points[i * stride..];
+        let p2 = &points[j * stride..];
+        if let Some(bundles) = bundles {
+            let strip = bundles[e].iter().map(|&[x, y, z]| Vec3D::new(x, y, z));
+            segments.push(LineStrip3D(strip.collect()));
+        } else if *directed {
+            arrows.push(Arrow3D {
+                origin: [p1[0], p1[1], p1[2]].into(),
+                vector: [p2[0] - p1[0], p2[1] - p1[1], p2[2] - p1[2]].into(),
+            });
+        } else {
+            segments.push(LineStrip3D(vec![
+                Vec3D::new(p1[0], p1[1], p1[2]),
+                Vec3D::new(p2[0], p2[1], p2[2]),
+            ]));
+        }
+        let color = match back_edges.as_ref().map(|b| b[e]) {
+            Some(true) => color_scheme.back_edge_color,
+            _ => color_scheme.edge_color(length),
+        };
+        let color = match highlighted.as_ref().map(|h| h[e]) {
+            None => color,
+            Some(true) => color_scheme.highlight_color,
+            Some(false) => color_scheme.faint(color),
+        };
+        let [r, g, b, a] = color.to_array();
+        let mut opacity = edge_styles[e].opacity();
+        if *strain_alpha {
+            let strain = color_scheme.strain(length);
+            opacity *= RELAXED_EDGE_OPACITY.lerp(1.0, strain);
+        }
+        let alpha = (a as f32 * opacity).round() as u8;
+        arrow_colors.push(ColorRGBA::from_unmultiplied_rgba(r, g, b, alpha));
+    }
+    assert_eq!(
+        arrows.len() + segments.len() + loops.len(),
+        edges_indices.len()
+    );
+    assert_eq!(arrow_colors.len(), arrows.len() + segments.len());
+    let sender = MsgSender::new(entity_path(entity_prefix, "edges")).with_time(timeline, step);
+    let sender = if *directed && bundles.is_none() {
+        sender.with_component(&arrows)?
+    } else {
+        sender.with_component(&segments)?
+    };
+    sender.with_component(&arrow_colors)?.send(session)?;
+    if !loops.is_empty() {
+        MsgSender::new(entity_path(entity_prefix, "self_loops"))
+            .with_time(timeline, step)
+            .with_component(&loops)?
+            .with_splat(ColorRGBA::from_rgb(0, 255, 0))?
+            .send(session)?;
+    }
+
+    if !edge_labels.is_empty() {
+        let positions = edge_labels
+            .iter()
+            .map(|&(e, _)| {
+                let (i, j) = edges_indices[e];
+                let p1 = &points[i * stride..];
+                let p2 = &points[j * stride..];
+                if i == j {
+                    // On top of the self-loop circle
+                    let r = 2.0 * styles[i].radius;
+                    Point3D::new(p1[0], p1[1] + 2.0 * r, p1[2])
                 } else {
-                    let t = ((length - edge_length) / 5.0).clamp(0.0, 1.0);
-                    ColorRGBA::from_rgb(0.lerp(127, t), 255.lerp(0, t), 0.lerp(255, t))
-                });
+                    Point3D::new(
+                        0.5 * (p1[0] + p2[0]),
+                        0.5 * (p1[1] + p2[1]),
+                        0.5 * (p1[2] + p2[2]),
+                    )
+                }
+            })
+            .collect::<Vec<_>>();
+        let labels = edge_labels
+            .iter()
+            .map(|(_, label)| label.clone())
+            .collect::<Vec<_>>();
+        MsgSender::new(entity_path(entity_prefix, "edge_labels"))
+            .with_time(timeline, step)
+            .with_component(&positions)?
+            .with_component(&labels)?
+            .with_splat(Radius(0.01))?
+            .send(session)?;
+    }
+
+    if !legend.is_empty() {
+        // A column next to the layout, starting level with its top
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for p in points.chunks(stride) {
+            for k in 0..3 {
+                min[k] = min[k].min(p[k]);
+                max[k] = max[k].max(p[k]);
             }
-            assert_eq!(arrows.len(), edges_indices.len());
-            assert_eq!(arrow_colors.len(), edges_indices.len());
-            MsgSender::new("edges")
-                .with_component(&arrows)?
-                .with_component(&arrow_colors)?
-                .send(&session)?;
         }
+        let positions = (0..legend.len())
+            .map(|row| Point3D {
+                x: min[0] - LEGEND_OFFSET,
+                y: min[1],
+                z: max[2] - LEGEND_SPACING * row as f32,
+            })
+            .collect::<Vec<_>>();
+        let colors = legend.iter().map(|&(_, color)| color).collect::<Vec<_>>();
+        let labels = legend
+            .iter()
+            .map(|(name, _)| Label(name.clone()))
+            .collect::<Vec<_>>();
+        MsgSender::new(entity_path(entity_prefix, "legend"))
+            .with_time(timeline, step)
+            .with_component(&positions)?
+            .with_component(&colors)?
+            .with_component(&labels)?
+            .with_splat(Radius(DEFAULT_STYLE.radius))?
+            .send(session)?;
+    }
+
+    if let Some(title) = title {
+        let bounds = bounding_box(points, stride);
+        let [x, y, _] = bounds.center();
+        MsgSender::new(entity_path(entity_prefix, "title"))
+            .with_time(timeline, step)
+            .with_component(&[Point3D::new(x, y, bounds.max[2] + TITLE_OFFSET)])?
+            .with_component(std::slice::from_ref(title))?
+            .with_splat(Radius(0.01))?
+            .send(session)?;
     }
 
-    // rerun::native_viewer::show(&session)?;
+    Ok(())
+}
+
+/// Height of the title above the layout.
+const TITLE_OFFSET: f32 = 1.0;
+
+/// Opacity of edges at their rest length with `--strain-alpha`.
+const RELAXED_EDGE_OPACITY: f32 = 0.15;
+
+/// Distance from the layout to the legend column.
+const LEGEND_OFFSET: f32 = 1.0;
+
+/// Distance between the rows of the legend.
+const LEGEND_SPACING: f32 = 0.3;
+
+/// The nodes drawn with one glyph, as parallel component lists.
+#[derive(Default)]
+struct NodeBatch {
+    positions: Vec<[f32; 3]>,
+    radii: Vec<f32>,
+    colors: Vec<ColorRGBA>,
+    labels: Vec<Label>,
+}
+
+/// Returns the coordinates `axes` of each point of `points`, which holds
+/// `stride` coordinates per node, as three coordinates per node.
+fn select_axes(points: &[f32], stride: usize, axes: [usize; 3]) -> Vec<f32> {
+    points
+        .chunks(stride)
+        .flat_map(|p| axes.map(|k| p[k]))
+        .collect()
+}
 
+/// Splits the nodes into a batch of spheres and a batch of boxes.
+fn node_batches(
+    points: &[f32],
+    stride: usize,
+    styles: &[ShapeStyle],
+    colors: &[ColorRGBA],
+    labels: &[Label],
+) -> [NodeBatch; 2] {
+    let mut spheres = NodeBatch::default();
+    let mut boxes = NodeBatch::default();
+    for (i, p) in points.chunks(stride).enumerate() {
+        let batch = match styles[i].glyph {
+            Glyph::Sphere => &mut spheres,
+            Glyph::Box => &mut boxes,
+        };
+        batch.positions.push([p[0], p[1], p[2]]);
+        batch.radii.push(styles[i].radius);
+        batch.colors.push(colors[i]);
+        batch.labels.push(labels[i].clone());
+    }
+    [spheres, boxes]
+}
+
+/// Sends a box outlining the whole layout.
+fn send_bounds(
+    session: &Session,
+    entity_prefix: &Option<String>,
+    bounds: &BoundingBox,
+    step: i64,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let [x, y, z] = bounds.size().map(|s| 0.5 * s);
+    let [cx, cy, cz] = bounds.center();
+    MsgSender::new(entity_path(entity_prefix, "bounds"))
+        .with_time(Timeline::new_sequence("iteration"), step)
+        .with_component(&[Box3D::new(x, y, z)])?
+        .with_component(&[Vec3D::new(cx, cy, cz)])?
+        .send(session)?;
     Ok(())
 }
 
-fn points_distance(points: &[[f32; MAX_DIMS]], i: usize, j: usize, dims: usize) -> f32 {
-    let p1 = &points[i];
-    let p2 = &points[j];
-    let mut length_squared: f32 = 0.0;
-    for k in 0..dims {
-        let dk = p2[k] - p1[k];
-        length_squared += dk * dk;
+fn entity_path(prefix: &Option<String>, path: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{}/{}", prefix, path),
+        None => path.to_string(),
     }
-    length_squared.sqrt()
+}
+
+/// Returns a closed circle of radius `r` that touches `p` from above.
+fn self_loop(p: [f32; 3], r: f32) -> LineStrip3D {
+    const SEGMENTS: usize = 16;
+    let points = (0..=SEGMENTS)
+        .map(|s| {
+            let angle = std::f32::consts::TAU * s as f32 / SEGMENTS as f32;
+            Vec3D::new(p[0] + r * angle.sin(), p[1] + r * (1.0 - angle.cos()), p[2])
+        })
+        .collect();
+    LineStrip3D(points)
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
 
-    use graphviz_rust::dot_generator::*;
-    use graphviz_rust::dot_structures::*;
-    use graphviz_rust::parse;
+    #[test]
+    fn shared_labels_test() {
+        let points = [0.0, 0.0, 0.0, 1.0, 2.0, 3.0];
+        let styles = [DEFAULT_STYLE, DEFAULT_STYLE];
+        let colors = [
+            ColorRGBA::from_rgb(255, 0, 0),
+            ColorRGBA::from_rgb(0, 0, 255),
+        ];
+        let labels = [Label("same".to_string()), Label("same".to_string())];
+        let [spheres, boxes] = node_batches(&points, 3, &styles, &colors, &labels);
+        assert_eq!(spheres.positions, vec![[0.0, 0.0, 0.0], [1.0, 2.0, 3.0]]);
+        assert_eq!(spheres.labels.len(), 2);
+        assert!(boxes.positions.is_empty());
+    }
 
     #[test]
-    fn parse_test() {
-        let g: Graph = parse(
-            r#"
-        strict digraph t {
-            aa[color=green]
-            subgraph v {
-                aa[shape=square]
-                subgraph vv{a2 -> b2}
-                aaa[color=red]
-                aaa -> bbb
-            }
-            aa -> be -> subgraph v { d -> aaa}
-            aa -> aaa -> v
-        }
-        "#,
-        )
-        .unwrap();
+    fn config_round_trip_test() {
+        let toml = toml::to_string(&Config::default()).unwrap();
+        let config: Config = toml::from_str(&toml).unwrap();
+        assert_eq!(config.layout, LayoutParams::default());
+        assert_eq!(config.colors.stretched_color.to_array(), [127, 0, 255, 255]);
 
+        let config: Config = toml::from_str("[layout]\nedge_length = 2.5\n").unwrap();
+        assert_eq!(config.layout.edge_length, 2.5);
+        assert_eq!(config.layout.max_dims, LayoutParams::default().max_dims);
+    }
+
+    #[test]
+    fn lerp_f32_test() {
+        assert_eq!(2.0.lerp(4.0, 0.0), 2.0);
+        assert_eq!(2.0.lerp(4.0, 0.5), 3.0);
+        assert_eq!(2.0.lerp(4.0, 1.0), 4.0);
+    }
+
+    #[test]
+    fn lerp_color_test() {
+        let a = ColorRGBA::from_unmultiplied_rgba(0, 255, 10, 255);
+        let b = ColorRGBA::from_unmultiplied_rgba(255, 0, 30, 0);
+        assert_eq!(a.lerp(b, 0.0).to_array(), [0, 255, 10, 255]);
+        assert_eq!(a.lerp(b, 1.0).to_array(), [255, 0, 30, 0]);
+        assert_eq!(a.lerp(b, 0.5).to_array(), [128, 128, 20, 128]);
+    }
+
+    #[test]
+    fn strain_test() {
+        let scheme = ColorScheme::default();
+        assert_eq!(scheme.strain(1.0), 0.0);
+        assert_eq!(scheme.strain(0.75), 0.5);
+        assert_eq!(scheme.strain(0.0), 1.0);
+        assert_eq!(scheme.strain(3.5), 0.5);
+        assert_eq!(scheme.strain(100.0), 1.0);
+    }
+
+    #[test]
+    fn render_axes_test() {
+        assert_eq!(parse_axes("3, 0,4"), Ok([3, 0, 4]));
+        assert!(parse_axes("0,1").is_err());
+        assert!(parse_axes("0,1,2,3").is_err());
+        assert!(parse_axes("0,x,2").is_err());
+        assert!(parse_axes("0,1,0").is_err());
+        let points = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0];
         assert_eq!(
-            g,
-            graph!(strict di id!("t");
-            node!("aa";attr!("color","green")),
-            subgraph!("v";
-            node!("aa"; attr!("shape","square")),
-            subgraph!("vv"; edge!(node_id!("a2") => node_id!("b2"))),
-            node!("aaa";attr!("color","red")),
-            edge!(node_id!("aaa") => node_id!("bbb"))
-                ),
-                edge!(node_id!("aa") => node_id!("be") => subgraph!("v"; edge!(node_id!("d") => node_id!("aaa")))),
-                edge!(node_id!("aa") => node_id!("aaa") => node_id!("v"))
-            )
-        )
+            select_axes(&points, 4, [3, 0, 1]),
+            vec![3.0, 0.0, 1.0, 7.0, 4.0, 5.0]
+        );
+    }
+
+    #[test]
+    fn ignored_attributes_test() {
+        let g = graphviz_rust::parse(r#"digraph { a [label=A, group=x]; b [group=y, tooltip=t] }"#)
+            .unwrap();
+        let flat = FlatGraph::new(&g);
+        let nodes = flat.nodes.iter().map(|n| n.attributes.as_slice());
+        assert_eq!(
+            ignored_attributes(nodes).as_deref(),
+            Some("group (2), tooltip (1)")
+        );
+        let g = graphviz_rust::parse("digraph { a [label=A, pos=\"1,2\"] }").unwrap();
+        let flat = FlatGraph::new(&g);
+        assert_eq!(
+            ignored_attributes(flat.nodes.iter().map(|n| n.attributes.as_slice())),
+            None
+        );
     }
 }
@@ -0,0 +1,165 @@
+//! Summary numbers for comparing the quality of layouts.
+
+use serde::Serialize;
+
+use crate::points_distance;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LayoutMetrics {
+    pub mean_edge_length: f32,
+    pub edge_length_stddev: f32,
+    /// Number of pairs of edges that cross when projected onto the XY plane.
+    /// Edges sharing a node are not counted.
+    pub edge_crossings: usize,
+    /// Mean distance from each node to its closest other node.
+    pub mean_nearest_neighbor_distance: f32,
+    pub bounds: BoundingBox,
+}
+
+/// Axis-aligned box around the first three coordinates of a layout.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BoundingBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl BoundingBox {
+    pub fn center(&self) -> [f32; 3] {
+        [0, 1, 2].map(|k| 0.5 * (self.min[k] + self.max[k]))
+    }
+
+    pub fn size(&self) -> [f32; 3] {
+        [0, 1, 2].map(|k| self.max[k] - self.min[k])
+    }
+}
+
+/// Returns the box around the first three coordinates of `points`, which holds
+/// `stride` coordinates per node. An empty layout has an empty box at the
+/// origin.
+pub fn bounding_box(points: &[f32], stride: usize) -> BoundingBox {
+    if points.is_empty() {
+        return BoundingBox {
+            min: [0.0; 3],
+            max: [0.0; 3],
+        };
+    }
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for p in points.chunks(stride) {
+        for k in 0..3 {
+            min[k] = min[k].min(p[k]);
+            max[k] = max[k].max(p[k]);
+        }
+    }
+    BoundingBox { min, max }
+}
+
+/// Computes the metrics over the first three coordinates of `points`, which
+/// holds `stride` coordinates per node. Self-loops are ignored.
+pub fn layout_metrics(
+    points: &[f32],
+    stride: usize,
+    edges_indices: &[(usize, usize)],
+) -> LayoutMetrics {
+    let edges = edges_indices
+        .iter()
+        .copied()
+        .filter(|(i, j)| i != j)
+        .collect::<Vec<_>>();
+    let lengths = edges
+        .iter()
+        .map(|&(i, j)| points_distance(points, stride, i, j, 3))
+        .collect::<Vec<_>>();
+    let (mean_edge_length, edge_length_stddev) = mean_and_stddev(&lengths);
+
+    let xy = |i: usize| [points[i * stride], points[i * stride + 1]];
+    let mut edge_crossings = 0;
+    for (a, &(i, j)) in edges.iter().enumerate() {
+        for &(k, l) in &edges[a + 1..] {
+            if i == k || i == l || j == k || j == l {
+                continue;
+            }
+            if segments_cross(xy(i), xy(j), xy(k), xy(l)) {
+                edge_crossings += 1;
+            }
+        }
+    }
+
+    let num_points = points.len() / stride;
+    let nearest = (0..num_points)
+        .filter_map(|i| {
+            (0..num_points)
+                .filter(|&j| j != i)
+                .map(|j| points_distance(points, stride, i, j, 3))
+                .min_by(f32::total_cmp)
+        })
+        .collect::<Vec<_>>();
+    let (mean_nearest_neighbor_distance, _) = mean_and_stddev(&nearest);
+
+    LayoutMetrics {
+        mean_edge_length,
+        edge_length_stddev,
+        edge_crossings,
+        mean_nearest_neighbor_distance,
+        bounds: bounding_box(points, stride),
+    }
+}
+
+fn mean_and_stddev(values: &[f32]) -> (f32, f32) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = values.len() as f32;
+    let mean = values.iter().sum::<f32>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+    (mean, variance.sqrt())
+}
+
+/// Returns true if segment `ab` properly intersects segment `cd`. Touching or
+/// collinear segments do not count.
+fn segments_cross(a: [f32; 2], b: [f32; 2], c: [f32; 2], d: [f32; 2]) -> bool {
+    let orient = |p: [f32; 2], q: [f32; 2], r: [f32; 2]| {
+        (q[0] - p[0]) * (r[1] - p[1]) - (q[1] - p[1]) * (r[0] - p[0])
+    };
+    let (d1, d2) = (orient(a, b, c), orient(a, b, d));
+    let (d3, d4) = (orient(c, d, a), orient(c, d, b));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layout_metrics_test() {
+        // A square with both diagonals, flat in XY
+        #[rustfmt::skip]
+        let points = [
+            0.0, 0.0, 0.0,
+            2.0, 0.0, 0.0,
+            2.0, 2.0, 0.0,
+            0.0, 2.0, 0.0,
+        ];
+        let edges = [(0, 1), (1, 2), (2, 3), (3, 0), (0, 2), (1, 3), (1, 1)];
+        let metrics = layout_metrics(&points, 3, &edges);
+        assert_eq!(metrics.edge_crossings, 1);
+        assert_eq!(metrics.mean_nearest_neighbor_distance, 2.0);
+        let diagonal = 8.0_f32.sqrt();
+        let mean = (4.0 * 2.0 + 2.0 * diagonal) / 6.0;
+        assert!((metrics.mean_edge_length - mean).abs() < 1e-5);
+        assert!(metrics.edge_length_stddev > 0.0);
+        assert_eq!(metrics.bounds.min, [0.0, 0.0, 0.0]);
+        assert_eq!(metrics.bounds.max, [2.0, 2.0, 0.0]);
+        assert_eq!(metrics.bounds.center(), [1.0, 1.0, 0.0]);
+        assert_eq!(metrics.bounds.size(), [2.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn empty_layout_metrics_test() {
+        let metrics = layout_metrics(&[], 3, &[]);
+        assert_eq!(metrics.edge_crossings, 0);
+        assert_eq!(metrics.mean_edge_length, 0.0);
+        assert_eq!(metrics.mean_nearest_neighbor_distance, 0.0);
+        assert_eq!(metrics.bounds.size(), [0.0; 3]);
+    }
+}
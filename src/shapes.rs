@@ -0,0 +1,125 @@
+//! How DOT node shapes and edge styles are drawn.
+//!
+//! New shapes only need an entry in [`SHAPE_STYLES`].
+
+/// Geometry used to draw a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyph {
+    Sphere,
+    Box,
+}
+
+/// Appearance of a node with a given shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeStyle {
+    pub glyph: Glyph,
+    /// Radius of spheres, or half the side of boxes.
+    pub radius: f32,
+    /// Fixed color for the shape, or `None` to pick one at random per shape.
+    pub color: Option<[u8; 3]>,
+}
+
+/// Style of nodes without a shape, or with a shape missing from the table.
+pub const DEFAULT_STYLE: ShapeStyle = ShapeStyle {
+    glyph: Glyph::Sphere,
+    radius: 0.05,
+    color: None,
+};
+
+const fn style(glyph: Glyph, radius: f32, color: [u8; 3]) -> ShapeStyle {
+    ShapeStyle {
+        glyph,
+        radius,
+        color: Some(color),
+    }
+}
+
+/// How an edge line is drawn, from its `style` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl LineStyle {
+    /// Parses a `style` value, which may list several styles separated by
+    /// commas. Styles other than dashed and dotted are drawn solid.
+    pub fn parse(style: Option<&str>) -> Self {
+        style
+            .into_iter()
+            .flat_map(|style| style.split(','))
+            .find_map(|style| match style.trim() {
+                "dashed" => Some(LineStyle::Dashed),
+                "dotted" => Some(LineStyle::Dotted),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Factor for the opacity of the edge. rerun draws no dash patterns, so
+    /// dashed and dotted edges are drawn fainter instead.
+    pub fn opacity(self) -> f32 {
+        match self {
+            LineStyle::Solid => 1.0,
+            LineStyle::Dashed => 0.5,
+            LineStyle::Dotted => 0.25,
+        }
+    }
+}
+
+/// Styles of the Graphviz node shapes.
+pub const SHAPE_STYLES: &[(&str, ShapeStyle)] = &[
+    ("box", style(Glyph::Box, 0.05, [31, 119, 180])),
+    ("rect", style(Glyph::Box, 0.05, [31, 119, 180])),
+    ("rectangle", style(Glyph::Box, 0.05, [31, 119, 180])),
+    ("square", style(Glyph::Box, 0.05, [174, 199, 232])),
+    ("box3d", style(Glyph::Box, 0.06, [31, 119, 180])),
+    ("record", style(Glyph::Box, 0.06, [255, 127, 14])),
+    ("Mrecord", style(Glyph::Box, 0.06, [255, 187, 120])),
+    ("component", style(Glyph::Box, 0.06, [44, 160, 44])),
+    ("folder", style(Glyph::Box, 0.06, [152, 223, 138])),
+    ("note", style(Glyph::Box, 0.05, [219, 219, 141])),
+    ("tab", style(Glyph::Box, 0.05, [188, 189, 34])),
+    ("ellipse", style(Glyph::Sphere, 0.05, [148, 103, 189])),
+    ("oval", style(Glyph::Sphere, 0.05, [148, 103, 189])),
+    ("circle", style(Glyph::Sphere, 0.05, [214, 39, 40])),
+    ("doublecircle", style(Glyph::Sphere, 0.07, [255, 152, 150])),
+    ("point", style(Glyph::Sphere, 0.02, [127, 127, 127])),
+    ("diamond", style(Glyph::Sphere, 0.06, [140, 86, 75])),
+    ("triangle", style(Glyph::Sphere, 0.06, [196, 156, 148])),
+    ("hexagon", style(Glyph::Sphere, 0.06, [227, 119, 194])),
+    ("octagon", style(Glyph::Sphere, 0.06, [247, 182, 210])),
+    ("cylinder", style(Glyph::Sphere, 0.06, [23, 190, 207])),
+    ("plaintext", style(Glyph::Sphere, 0.03, [199, 199, 199])),
+    ("plain", style(Glyph::Sphere, 0.03, [199, 199, 199])),
+    ("none", style(Glyph::Sphere, 0.03, [199, 199, 199])),
+];
+
+/// Returns the style for the DOT `shape` value.
+pub fn shape_style(shape: &str) -> ShapeStyle {
+    SHAPE_STYLES
+        .iter()
+        .find(|(name, _)| *name == shape)
+        .map(|&(_, style)| style)
+        .unwrap_or(DEFAULT_STYLE)
+}
+
+/// Returns a radius between `min_radius` and `max_radius` for each node that
+/// grows with the square root of its degree, so hubs stand out without
+/// becoming enormous.
+pub fn degree_radii(degrees: &[usize], min_radius: f32, max_radius: f32) -> Vec<f32> {
+    let degrees = degrees.iter().map(|&d| d as f32).collect::<Vec<_>>();
+    score_radii(&degrees, min_radius, max_radius)
+}
+
+/// Like [`degree_radii`], but for any non-negative score, such as PageRank.
+pub fn score_radii(scores: &[f32], min_radius: f32, max_radius: f32) -> Vec<f32> {
+    let max_score = scores.iter().copied().fold(0.0, f32::max);
+    let max_score = if max_score > 0.0 { max_score } else { 1.0 };
+    scores
+        .iter()
+        .map(|&s| min_radius + (max_radius - min_radius) * (s / max_score).sqrt())
+        .collect()
+}